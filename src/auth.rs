@@ -0,0 +1,273 @@
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::config::AuthConfig;
+
+/// Header naming the HMAC key a request was signed with
+pub const HMAC_KEY_ID_HEADER: &str = "X-Auth-Key-Id";
+/// Header carrying the unix time, in seconds, at which a request was signed
+pub const HMAC_TIMESTAMP_HEADER: &str = "X-Auth-Timestamp";
+/// Header carrying the hex-encoded HMAC-SHA256 signature of a request
+pub const HMAC_SIGNATURE_HEADER: &str = "X-Auth-Signature";
+
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("Missing credentials")]
+    MissingCredentials,
+
+    #[error("Invalid credentials: {0}")]
+    InvalidCredentials(String),
+}
+
+/// The parts of a request that credentials are checked against
+pub struct AuthRequest<'a> {
+    pub method: &'a str,
+    /// Path and query string, as signed by HMAC clients
+    pub target: &'a str,
+    pub headers: &'a [(String, String)],
+    pub body: &'a [u8],
+}
+
+impl AuthRequest<'_> {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn bearer_token(&self) -> Option<&str> {
+        let value = self.header("Authorization")?;
+        let (scheme, token) = value.split_once(' ')?;
+        scheme
+            .eq_ignore_ascii_case("bearer")
+            .then_some(token.trim())
+    }
+}
+
+/// Bytes an HMAC client signs: timestamp, method and target on their own lines, then the body
+pub fn hmac_signing_payload(timestamp: u64, method: &str, target: &str, body: &[u8]) -> Vec<u8> {
+    let mut payload = format!(
+        "{}\n{}\n{}\n",
+        timestamp,
+        method.to_ascii_uppercase(),
+        target
+    )
+    .into_bytes();
+    payload.extend_from_slice(body);
+    payload
+}
+
+enum Scheme {
+    Bearer {
+        // token -> principal
+        tokens: Vec<(String, String)>,
+    },
+    Hmac {
+        // key id -> (secret, principal)
+        keys: HashMap<String, (Vec<u8>, String)>,
+        max_skew_secs: u64,
+    },
+    Jwt {
+        jwks: JwkSet,
+        issuer: Option<String>,
+        audience: Option<String>,
+        algorithms: Vec<Algorithm>,
+    },
+}
+
+/// Checks request credentials for a handler, returning the authenticated principal
+pub struct Authenticator {
+    scheme: Scheme,
+}
+
+impl Authenticator {
+    pub fn new(config: &AuthConfig) -> Result<Self> {
+        let scheme = match config {
+            AuthConfig::Bearer { tokens } => Scheme::Bearer {
+                tokens: tokens
+                    .iter()
+                    .map(|t| (t.token.clone(), t.principal.clone()))
+                    .collect(),
+            },
+            AuthConfig::Hmac {
+                keys,
+                max_skew_secs,
+            } => Scheme::Hmac {
+                keys: keys
+                    .iter()
+                    .map(|key| {
+                        let principal = key.principal.clone().unwrap_or(key.key_id.clone());
+                        (
+                            key.key_id.clone(),
+                            (key.secret.as_bytes().to_vec(), principal),
+                        )
+                    })
+                    .collect(),
+                max_skew_secs: *max_skew_secs,
+            },
+            AuthConfig::Jwt {
+                jwks_path,
+                issuer,
+                audience,
+                algorithms,
+            } => {
+                let jwks = std::fs::read_to_string(jwks_path)
+                    .with_context(|| format!("Failed to read JWKS from {}", jwks_path.display()))?;
+                Scheme::Jwt {
+                    jwks: serde_json::from_str(&jwks).context("Invalid JWKS")?,
+                    issuer: issuer.clone(),
+                    audience: audience.clone(),
+                    algorithms: algorithms.clone(),
+                }
+            }
+        };
+        Ok(Self { scheme })
+    }
+
+    /// Whether a rejected caller should be told to present a bearer token
+    pub fn uses_bearer(&self) -> bool {
+        !matches!(self.scheme, Scheme::Hmac { .. })
+    }
+
+    /// Whether credentials cover the body, which must then be read before
+    /// authenticating. Other schemes only look at the headers.
+    pub fn needs_body(&self) -> bool {
+        matches!(self.scheme, Scheme::Hmac { .. })
+    }
+
+    pub fn authenticate(&self, request: &AuthRequest<'_>) -> Result<String, AuthError> {
+        match &self.scheme {
+            Scheme::Bearer { tokens } => {
+                let presented = request
+                    .bearer_token()
+                    .ok_or(AuthError::MissingCredentials)?;
+                tokens
+                    .iter()
+                    .find(|(token, _)| constant_time_eq(token.as_bytes(), presented.as_bytes()))
+                    .map(|(_, principal)| principal.clone())
+                    .ok_or_else(|| AuthError::InvalidCredentials("unknown token".to_string()))
+            }
+            Scheme::Hmac {
+                keys,
+                max_skew_secs,
+            } => Self::verify_hmac(request, keys, *max_skew_secs),
+            Scheme::Jwt {
+                jwks,
+                issuer,
+                audience,
+                algorithms,
+            } => Self::verify_jwt(
+                request,
+                jwks,
+                issuer.as_deref(),
+                audience.as_deref(),
+                algorithms,
+            ),
+        }
+    }
+
+    fn verify_hmac(
+        request: &AuthRequest<'_>,
+        keys: &HashMap<String, (Vec<u8>, String)>,
+        max_skew_secs: u64,
+    ) -> Result<String, AuthError> {
+        let (Some(key_id), Some(timestamp), Some(signature)) = (
+            request.header(HMAC_KEY_ID_HEADER),
+            request.header(HMAC_TIMESTAMP_HEADER),
+            request.header(HMAC_SIGNATURE_HEADER),
+        ) else {
+            return Err(AuthError::MissingCredentials);
+        };
+        let invalid = |reason: &str| AuthError::InvalidCredentials(reason.to_string());
+
+        let (secret, principal) = keys.get(key_id).ok_or_else(|| invalid("unknown key"))?;
+        let timestamp: u64 = timestamp.parse().map_err(|_| invalid("bad timestamp"))?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        if now.abs_diff(timestamp) > max_skew_secs {
+            return Err(invalid("timestamp outside the allowed window"));
+        }
+        let signature = hex::decode(signature).map_err(|_| invalid("bad signature encoding"))?;
+
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret).map_err(|_| invalid("unusable key"))?;
+        mac.update(&hmac_signing_payload(
+            timestamp,
+            request.method,
+            request.target,
+            request.body,
+        ));
+        mac.verify_slice(&signature)
+            .map_err(|_| invalid("signature mismatch"))?;
+
+        Ok(principal.clone())
+    }
+
+    fn verify_jwt(
+        request: &AuthRequest<'_>,
+        jwks: &JwkSet,
+        issuer: Option<&str>,
+        audience: Option<&str>,
+        algorithms: &[Algorithm],
+    ) -> Result<String, AuthError> {
+        let token = request
+            .bearer_token()
+            .ok_or(AuthError::MissingCredentials)?;
+        let invalid = |reason: String| AuthError::InvalidCredentials(reason);
+
+        let header = jsonwebtoken::decode_header(token).map_err(|e| invalid(e.to_string()))?;
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| invalid("no matching key".to_string()))?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|e| invalid(e.to_string()))?;
+
+        // The key, or the configuration, decides the algorithm, never the token
+        let allowed = match jwk.common.key_algorithm {
+            Some(alg) => vec![Algorithm::from_str(&alg.to_string())
+                .map_err(|_| invalid(format!("key algorithm {} cannot sign tokens", alg)))?],
+            None => algorithms.to_vec(),
+        };
+        if !allowed.contains(&header.alg) {
+            return Err(invalid(format!(
+                "algorithm {:?} is not allowed for this key",
+                header.alg
+            )));
+        }
+
+        let mut validation = Validation::new(header.alg);
+        validation.algorithms = allowed;
+        if let Some(issuer) = issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        let claims = jsonwebtoken::decode::<Value>(token, &key, &validation)
+            .map_err(|e| invalid(e.to_string()))?
+            .claims;
+        claims["sub"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| invalid("token has no subject".to_string()))
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -1,8 +1,10 @@
 use anyhow::Result;
 use serde_json::Value;
-use tracing::{error, info};
-use wasmtime::component::{Component, ComponentExportIndex, Linker};
+use tracing::{error, info, Instrument, Span};
+use wasmtime::component::{Component, ComponentExportIndex, Linker, Resource, ResourceType};
 
+use crate::http_server::RequestBody;
+use crate::registry::ACTOR_REGISTRY;
 use crate::store::Store;
 
 /// Represents a set of capabilities that a WASM component can implement
@@ -57,6 +59,77 @@ pub trait ActorCapability: Send {
     }
 }
 
+/// Host implementation of `send`, resolving the target through the actor registry
+fn send_to_actor(
+    mut ctx: wasmtime::StoreContextMut<'_, Store>,
+    (address, msg): (String, Vec<u8>),
+) -> Result<()> {
+    // Convert message bytes to JSON Value
+    let msg_value: Value = serde_json::from_slice(&msg).map_err(|e| {
+        error!("Failed to parse message as JSON: {}", e);
+        wasmtime::Error::msg("Invalid message format")
+    })?;
+
+    // Get store reference
+    let store = ctx.data_mut();
+    let http = store.http.clone();
+
+    // Spawn task to send message since we can't await in this context. It
+    // stays in the sender's span so the trace continues at the receiver.
+    tokio::spawn(
+        async move {
+            if let Err(e) = ACTOR_REGISTRY
+                .send(&address, msg_value, http.as_ref())
+                .await
+            {
+                error!("Failed to send message: {}", e);
+            }
+        }
+        .instrument(Span::current()),
+    );
+
+    Ok(())
+}
+
+/// Host implementation of `spawn`, starting a child actor from a manifest or component
+fn spawn_child(
+    mut ctx: wasmtime::StoreContextMut<'_, Store>,
+    (reference, init_args): (String, Vec<u8>),
+) -> Result<(String,)> {
+    let init_args: Value = if init_args.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&init_args).map_err(|e| {
+            error!("Failed to parse init args as JSON: {}", e);
+            wasmtime::Error::msg("Invalid init args format")
+        })?
+    };
+
+    let supervisor = ctx
+        .data_mut()
+        .supervisor
+        .clone()
+        .ok_or_else(|| wasmtime::Error::msg("Actor cannot spawn children"))?;
+
+    let child_id = supervisor.spawn_child(&reference, init_args).map_err(|e| {
+        error!("Failed to spawn child actor from {}: {}", reference, e);
+        e
+    })?;
+
+    Ok((child_id,))
+}
+
+/// Host implementation of `stop`, shutting down a child actor
+fn stop_child(mut ctx: wasmtime::StoreContextMut<'_, Store>, (actor_id,): (String,)) -> Result<()> {
+    let supervisor = ctx
+        .data_mut()
+        .supervisor
+        .clone()
+        .ok_or_else(|| wasmtime::Error::msg("Actor has no children"))?;
+
+    supervisor.stop_child(&actor_id)
+}
+
 /// The base actor capability that all actors must implement
 pub struct BaseActorCapability;
 
@@ -74,35 +147,11 @@ impl ActorCapability for BaseActorCapability {
         )?;
 
         // Add send function
-        runtime.func_wrap(
-            "send",
-            |mut ctx: wasmtime::StoreContextMut<'_, Store>, (address, msg): (String, Vec<u8>)| {
-                // Convert message bytes to JSON Value
-                let msg_value: Value = serde_json::from_slice(&msg).map_err(|e| {
-                    error!("Failed to parse message as JSON: {}", e);
-                    wasmtime::Error::msg("Invalid message format")
-                })?;
-
-                // Get store reference
-                let store = ctx.data_mut();
-
-                // If we have HTTP host, send the message
-                if let Some(http) = &store.http {
-                    // Spawn task to send message since we can't await in this context
-                    let http = http.clone();
-                    let address = address.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = http.send_message(address, msg_value).await {
-                            error!("Failed to send message: {}", e);
-                        }
-                    });
-                } else {
-                    error!("No HTTP host available for sending messages");
-                }
+        runtime.func_wrap("send", send_to_actor)?;
 
-                Ok(())
-            },
-        )?;
+        // Add child lifecycle functions
+        runtime.func_wrap("spawn", spawn_child)?;
+        runtime.func_wrap("stop", stop_child)?;
 
         Ok(())
     }
@@ -143,93 +192,143 @@ impl ActorCapability for BaseActorCapability {
     }
 }
 
-/// HTTP actor capability
-pub struct HttpCapability;
+/// Interface implemented by HTTP actors that exchange JSON-encoded requests and responses
+pub const HTTP_ACTOR_INTERFACE: &str = "ntwk:simple-http-actor/http-actor";
+
+/// Interface implemented by HTTP actors that exchange typed request and response records
+pub const TYPED_HTTP_ACTOR_INTERFACE: &str = "ntwk:simple-http-actor/http-actor@0.2.0";
+
+/// Host implementation of `take-request-body`. Each call handles a single
+/// request, so the handle needs no table and always refers to that body.
+fn take_request_body(
+    ctx: wasmtime::StoreContextMut<'_, Store>,
+    (): (),
+) -> Result<(Option<Resource<RequestBody>>,)> {
+    let taken = ctx
+        .data()
+        .request_body
+        .as_ref()
+        .is_some_and(|body| body.take());
+    Ok((taken.then(|| Resource::new_own(0)),))
+}
 
-impl ActorCapability for HttpCapability {
-    fn setup_host_functions(&self, linker: &mut Linker<Store>) -> Result<()> {
-        let mut runtime = linker.instance("ntwk:simple-http-actor/http-runtime")?;
+/// Host implementation of `[method]request-body.read`
+fn read_request_body(
+    ctx: wasmtime::StoreContextMut<'_, Store>,
+    (_, max_bytes): (Resource<RequestBody>, u32),
+) -> Result<(Result<Option<Vec<u8>>, String>,)> {
+    let body = ctx
+        .data()
+        .request_body
+        .clone()
+        .ok_or_else(|| wasmtime::Error::msg("No request body to read"))?;
+    Ok((body.read(max_bytes as usize).map_err(|e| e.to_string()),))
+}
 
-        // Add log function
-        runtime.func_wrap(
-            "log",
-            |_: wasmtime::StoreContextMut<'_, Store>, (msg,): (String,)| {
-                info!("[WASM] {}", msg);
-                Ok(())
-            },
-        )?;
+fn setup_http_runtime(linker: &mut Linker<Store>, runtime_name: &str) -> Result<()> {
+    let mut runtime = linker.instance(runtime_name)?;
+
+    // Add log function
+    runtime.func_wrap(
+        "log",
+        |_: wasmtime::StoreContextMut<'_, Store>, (msg,): (String,)| {
+            info!("[WASM] {}", msg);
+            Ok(())
+        },
+    )?;
+
+    // Add send function - reuse same implementation as BaseActorCapability
+    runtime.func_wrap("send", send_to_actor)?;
+
+    // Add child lifecycle functions
+    runtime.func_wrap("spawn", spawn_child)?;
+    runtime.func_wrap("stop", stop_child)?;
+
+    // Add streamed request bodies; dropping the handle leaves the rest unread
+    runtime.resource(
+        "request-body",
+        ResourceType::host::<RequestBody>(),
+        |_, _| Ok(()),
+    )?;
+    runtime.func_wrap("take-request-body", take_request_body)?;
+    runtime.func_wrap("[method]request-body.read", read_request_body)?;
+
+    Ok(())
+}
 
-        // Add send function - reuse same implementation as BaseActorCapability
-        runtime.func_wrap(
-            "send",
-            |mut ctx: wasmtime::StoreContextMut<'_, Store>, (address, msg): (String, Vec<u8>)| {
-                let msg_value: Value = serde_json::from_slice(&msg).map_err(|e| {
-                    error!("Failed to parse message as JSON: {}", e);
-                    wasmtime::Error::msg("Invalid message format")
-                })?;
-
-                let store = ctx.data_mut();
-
-                if let Some(http) = &store.http {
-                    let http = http.clone();
-                    let address = address.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = http.send_message(address, msg_value).await {
-                            error!("Failed to send message: {}", e);
-                        }
-                    });
-                } else {
-                    error!("No HTTP host available for sending messages");
-                }
+fn http_actor_exports(
+    component: &Component,
+    interface_name: &str,
+) -> Result<Vec<(String, ComponentExportIndex)>> {
+    let (_, instance) = component
+        .export_index(None, interface_name)
+        .expect("Failed to get HTTP actor instance");
+
+    let mut exports = Vec::new();
+    // Get required function exports
+    let (_, init) = component
+        .export_index(Some(&instance), "init")
+        .expect("Failed to get init export");
+    exports.push(("init".to_string(), init));
+
+    let (_, handle) = component
+        .export_index(Some(&instance), "handle")
+        .expect("Failed to get handle export");
+    exports.push(("handle".to_string(), handle));
+
+    let (_, state_contract) = component
+        .export_index(Some(&instance), "state-contract")
+        .expect("Failed to get state contract export");
+    exports.push(("state-contract".to_string(), state_contract));
+
+    let (_, message_contract) = component
+        .export_index(Some(&instance), "message-contract")
+        .expect("Failed to get message contract export");
+    exports.push(("message-contract".to_string(), message_contract));
+
+    let (_, http_contract) = component
+        .export_index(Some(&instance), "http-contract")
+        .expect("Failed to get HTTP contract export");
+    exports.push(("http-contract".to_string(), http_contract));
+
+    let (_, handle_http) = component
+        .export_index(Some(&instance), "handle-http")
+        .expect("Failed to get HTTP handler export");
+    exports.push(("handle-http".to_string(), handle_http));
+
+    Ok(exports)
+}
 
-                Ok(())
-            },
-        )?;
+/// HTTP actor capability
+pub struct HttpCapability;
 
-        Ok(())
+impl ActorCapability for HttpCapability {
+    fn setup_host_functions(&self, linker: &mut Linker<Store>) -> Result<()> {
+        setup_http_runtime(linker, "ntwk:simple-http-actor/http-runtime")
     }
 
     fn get_exports(&self, component: &Component) -> Result<Vec<(String, ComponentExportIndex)>> {
-        let (_, instance) = component
-            .export_index(None, "ntwk:simple-http-actor/http-actor")
-            .expect("Failed to get HTTP actor instance");
-
-        let mut exports = Vec::new();
-        // Get required function exports
-        let (_, init) = component
-            .export_index(Some(&instance), "init")
-            .expect("Failed to get init export");
-        exports.push(("init".to_string(), init));
-
-        let (_, handle) = component
-            .export_index(Some(&instance), "handle")
-            .expect("Failed to get handle export");
-        exports.push(("handle".to_string(), handle));
-
-        let (_, state_contract) = component
-            .export_index(Some(&instance), "state-contract")
-            .expect("Failed to get state contract export");
-        exports.push(("state-contract".to_string(), state_contract));
+        http_actor_exports(component, HTTP_ACTOR_INTERFACE)
+    }
 
-        let (_, message_contract) = component
-            .export_index(Some(&instance), "message-contract")
-            .expect("Failed to get message contract export");
-        exports.push(("message-contract".to_string(), message_contract));
+    fn interface_name(&self) -> &str {
+        HTTP_ACTOR_INTERFACE
+    }
+}
 
-        let (_, http_contract) = component
-            .export_index(Some(&instance), "http-contract")
-            .expect("Failed to get HTTP contract export");
-        exports.push(("http-contract".to_string(), http_contract));
+/// HTTP actor capability using typed request and response records
+pub struct TypedHttpCapability;
 
-        let (_, handle_http) = component
-            .export_index(Some(&instance), "handle-http")
-            .expect("Failed to get HTTP handler export");
-        exports.push(("handle-http".to_string(), handle_http));
+impl ActorCapability for TypedHttpCapability {
+    fn setup_host_functions(&self, linker: &mut Linker<Store>) -> Result<()> {
+        setup_http_runtime(linker, "ntwk:simple-http-actor/http-runtime@0.2.0")
+    }
 
-        Ok(exports)
+    fn get_exports(&self, component: &Component) -> Result<Vec<(String, ComponentExportIndex)>> {
+        http_actor_exports(component, TYPED_HTTP_ACTOR_INTERFACE)
     }
 
     fn interface_name(&self) -> &str {
-        "ntwk:simple-http-actor/http-actor"
+        TYPED_HTTP_ACTOR_INTERFACE
     }
 }
@@ -1,35 +1,110 @@
 use crate::chain_emitter::CHAIN_EMITTER;
 use crate::logging::ChainEventType;
+use crate::metrics::METRICS;
 use crate::{ActorInput, ActorOutput};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use md5;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use tracing::debug;
 
+// Events are created once and moved into the chain, so boxing inputs would gain little
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ChainEvent {
     ExternalInput {
         input: ActorInput,
-        timestamp: chrono::DateTime<Utc>,
+        /// Caller authenticated by the handler that received the input
+        #[serde(default)]
+        principal: Option<String>,
+        timestamp: DateTime<Utc>,
     },
     ActorMessage {
         source_actor: String,
         source_chain_state: String,
         content: Value,
-        timestamp: chrono::DateTime<Utc>,
+        timestamp: DateTime<Utc>,
     },
     StateChange {
         old_state: Value,
         new_state: Value,
-        timestamp: chrono::DateTime<Utc>,
+        timestamp: DateTime<Utc>,
     },
     Output {
         output: ActorOutput,
         chain_state: String,
-        timestamp: chrono::DateTime<Utc>,
+        timestamp: DateTime<Utc>,
     },
+    ParentLink {
+        parent_actor: String,
+        timestamp: DateTime<Utc>,
+    },
+    ChildSpawned {
+        child_id: String,
+        manifest: String,
+        timestamp: DateTime<Utc>,
+    },
+    ChildStopped {
+        child_id: String,
+        timestamp: DateTime<Utc>,
+    },
+    ActorUpgraded {
+        component: String,
+        timestamp: DateTime<Utc>,
+    },
+    /// The output at hash `output` was produced after its HTTP caller stopped waiting
+    ResponseAbandoned {
+        correlation_id: String,
+        output: String,
+        timestamp: DateTime<Utc>,
+    },
+    Error {
+        message: String,
+        correlation_id: Option<String>,
+        timestamp: DateTime<Utc>,
+    },
+    Shutdown {
+        drained: usize,
+        abandoned: usize,
+        timestamp: DateTime<Utc>,
+    },
+}
+
+impl ChainEvent {
+    /// Type this event is published under, for a chain that already has entries
+    pub fn event_type(&self) -> ChainEventType {
+        match self {
+            ChainEvent::ExternalInput { .. } => ChainEventType::Input,
+            ChainEvent::ActorMessage { .. } => ChainEventType::Message,
+            ChainEvent::StateChange { .. } => ChainEventType::StateTransition,
+            ChainEvent::Output { .. } => ChainEventType::Output,
+            ChainEvent::ParentLink { .. }
+            | ChainEvent::ChildSpawned { .. }
+            | ChainEvent::ChildStopped { .. }
+            | ChainEvent::ActorUpgraded { .. }
+            | ChainEvent::Shutdown { .. } => ChainEventType::Lifecycle,
+            ChainEvent::ResponseAbandoned { .. } | ChainEvent::Error { .. } => {
+                ChainEventType::Error
+            }
+        }
+    }
+
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            ChainEvent::ExternalInput { timestamp, .. }
+            | ChainEvent::ActorMessage { timestamp, .. }
+            | ChainEvent::StateChange { timestamp, .. }
+            | ChainEvent::Output { timestamp, .. }
+            | ChainEvent::ParentLink { timestamp, .. }
+            | ChainEvent::ChildSpawned { timestamp, .. }
+            | ChainEvent::ChildStopped { timestamp, .. }
+            | ChainEvent::ActorUpgraded { timestamp, .. }
+            | ChainEvent::ResponseAbandoned { timestamp, .. }
+            | ChainEvent::Error { timestamp, .. }
+            | ChainEvent::Shutdown { timestamp, .. } => *timestamp,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,13 +115,16 @@ pub struct ChainEntry {
 
 #[derive(Debug)]
 pub struct HashChain {
+    /// Actor whose events this chain records, named in emitted events
+    actor_name: String,
     head: Option<String>,
     entries: HashMap<String, ChainEntry>,
 }
 
 impl HashChain {
-    pub fn new() -> Self {
+    pub fn new(actor_name: &str) -> Self {
         Self {
+            actor_name: actor_name.to_string(),
             head: None,
             entries: HashMap::new(),
         }
@@ -64,12 +142,12 @@ impl HashChain {
         // Emit logging event
         CHAIN_EMITTER.emit(crate::logging::ChainEvent {
             hash: hash.clone(),
-            timestamp: Utc::now(),
-            actor_name: "unknown".to_string(), // TODO: Store actor name in chain
+            timestamp: entry.event.timestamp(),
+            actor_name: self.actor_name.clone(),
             event_type: if self.head.is_none() {
                 ChainEventType::Init
             } else {
-                ChainEventType::StateTransition
+                entry.event.event_type()
             },
             data: serde_json::to_value(&entry).unwrap(),
             parent: self.head.clone(),
@@ -79,6 +157,9 @@ impl HashChain {
 
         self.entries.insert(hash.clone(), entry);
         self.head = Some(hash.clone());
+        METRICS
+            .chain_length
+            .set(&[("actor", self.actor_name.as_str())], self.entries.len() as f64);
 
         hash
     }
@@ -88,31 +169,45 @@ impl HashChain {
     }
 
     pub fn get_current_state(&self) -> Option<Value> {
-        let mut current = self.head.as_ref()?;
-        
-        while let Some(entry) = self.entries.get(current) {
-            if let ChainEvent::StateChange { new_state, .. } = &entry.event {
-                return Some(new_state.clone());
-            }
-            if let Some(parent) = &entry.parent {
-                current = parent;
-            } else {
-                break;
-            }
-        }
-        None
+        self.get_state_at(self.head.as_deref()?)
     }
 
-    pub fn get_full_chain(&self) -> Vec<(String, ChainEntry)> {
-        let mut result = Vec::new();
-        let mut current = self.head.clone();
+    /// State as of the entry at `hash`: the newest state change at or before it
+    pub fn get_state_at(&self, hash: &str) -> Option<Value> {
+        self.entries_from(Some(hash))
+            .find_map(|(_, entry)| match &entry.event {
+                ChainEvent::StateChange { new_state, .. } => Some(new_state.clone()),
+                _ => None,
+            })
+    }
 
-        while let Some(hash) = current {
-            let entry = self.entries.get(&hash).expect("Chain corrupted").clone();
-            result.push((hash.clone(), entry.clone()));
-            current = entry.parent;
-        }
+    pub fn get_entry(&self, hash: &str) -> Option<&ChainEntry> {
+        self.entries.get(hash)
+    }
 
-        result
+    pub fn len(&self) -> usize {
+        self.entries.len()
     }
-}
\ No newline at end of file
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries from `hash` back to the first, newest first
+    pub fn entries_from<'a>(
+        &'a self,
+        hash: Option<&'a str>,
+    ) -> impl Iterator<Item = (&'a str, &'a ChainEntry)> + 'a {
+        let first = hash.and_then(|hash| Some((hash, self.entries.get(hash)?)));
+        std::iter::successors(first, move |(_, entry)| {
+            let parent = entry.parent.as_deref()?;
+            Some((parent, self.entries.get(parent).expect("Chain corrupted")))
+        })
+    }
+
+    pub fn get_full_chain(&self) -> Vec<(String, ChainEntry)> {
+        self.entries_from(self.get_head())
+            .map(|(hash, entry)| (hash.to_string(), entry.clone()))
+            .collect()
+    }
+}
@@ -1,15 +1,27 @@
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
-use chrono::Utc;
 use tokio::sync::broadcast;
 use serde::Serialize;
+use tracing::info;
 
-use crate::logging::ChainEvent;
+use crate::logging::{ChainEvent, CHAIN_TARGET};
+
+/// Stored events to replay ahead of live ones, from [`ChainEmitter::resume_after`]
+pub struct Replay {
+    pub events: Vec<ChainEvent>,
+    /// False when the requested event had aged out, in which case `events`
+    /// is the whole history and earlier events were missed
+    pub complete: bool,
+    /// Events emitted after the last one in `events`
+    pub rx: broadcast::Receiver<ChainEvent>,
+}
 
 pub struct ChainEmitter {
     history: Mutex<VecDeque<ChainEvent>>,
     max_history: usize,
     tx: broadcast::Sender<ChainEvent>,
+    log_events: AtomicBool,
 }
 
 impl ChainEmitter {
@@ -19,9 +31,15 @@ impl ChainEmitter {
             history: Mutex::new(VecDeque::with_capacity(max_history)),
             max_history,
             tx,
+            log_events: AtomicBool::new(false),
         }
     }
 
+    /// Log each event as it is emitted, as well as publishing it
+    pub fn set_log_events(&self, enabled: bool) {
+        self.log_events.store(enabled, Ordering::Relaxed);
+    }
+
     pub fn emit(&self, event: ChainEvent) {
         // Store in history
         let mut history = self.history.lock().unwrap();
@@ -33,9 +51,17 @@ impl ChainEmitter {
         // Broadcast to all subscribers
         let _ = self.tx.send(event.clone());
 
-        // Print to stdout in a clearly marked format
-        println!("\n[CHAIN] Event at {}", Utc::now().to_rfc3339());
-        println!("{}", event);
+        if self.log_events.load(Ordering::Relaxed) {
+            info!(
+                target: CHAIN_TARGET,
+                hash = %event.hash,
+                actor = %event.actor_name,
+                event_type = ?event.event_type,
+                parent = event.parent.as_deref(),
+                data = %event.data,
+                "[CHAIN] Event committed"
+            );
+        }
     }
 
     pub fn subscribe(&self) -> broadcast::Receiver<ChainEvent> {
@@ -45,9 +71,33 @@ impl ChainEmitter {
     pub fn get_history(&self) -> Vec<ChainEvent> {
         self.history.lock().unwrap().iter().cloned().collect()
     }
+
+    /// Events emitted after the one with `hash`, or `None` if that event is
+    /// no longer in the history
+    pub fn history_after(&self, hash: &str) -> Option<Vec<ChainEvent>> {
+        let history = self.history.lock().unwrap();
+        let position = history.iter().position(|event| event.hash == hash)?;
+        Some(history.iter().skip(position + 1).cloned().collect())
+    }
+
+    /// Stored events after the one with `hash`, together with a subscription
+    /// to every event emitted after them. Events are emitted under the
+    /// history lock, so none are missed or repeated between the two.
+    pub fn resume_after(&self, hash: &str) -> Replay {
+        let history = self.history.lock().unwrap();
+        let (events, complete) = match history.iter().position(|event| event.hash == hash) {
+            Some(position) => (history.iter().skip(position + 1).cloned().collect(), true),
+            None => (history.iter().cloned().collect(), false),
+        };
+        Replay {
+            events,
+            complete,
+            rx: self.tx.subscribe(),
+        }
+    }
 }
 
 // Global instance
 lazy_static::lazy_static! {
     pub static ref CHAIN_EMITTER: ChainEmitter = ChainEmitter::new(1000);
-}
\ No newline at end of file
+}
@@ -1,6 +1,12 @@
+use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::path::PathBuf;
 
+use crate::listener;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ManifestConfig {
     pub name: String,
@@ -11,15 +17,31 @@ pub struct ManifestConfig {
     pub handlers: Vec<HandlerConfig>,
     #[serde(default)]
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub mailbox: MailboxConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    /// Logical actor names mapped to the endpoints they are reachable at
+    #[serde(default)]
+    pub registry: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct LoggingConfig {
+    /// Log every chain commit, under the `theater::chain` target
     pub chain_events: bool,
     pub level: String,
     pub output: LogOutput,
+    /// File written when `output` is `file`
     pub file_path: Option<PathBuf>,
+    pub format: LogFormat,
+    /// Size at which the log file is rotated, or 0 to never rotate
+    pub max_file_bytes: u64,
+    /// Rotated log files kept alongside the current one
+    pub max_files: usize,
+    /// Export spans over OpenTelemetry when set
+    pub tracing: Option<TracingConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,13 +51,85 @@ pub enum LogOutput {
     File,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One compact, human readable line per event
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
 impl Default for LoggingConfig {
     fn default() -> Self {
         Self {
-            chain_events: true,
+            chain_events: false,
             level: "info".to_string(),
             output: LogOutput::Stdout,
             file_path: None,
+            format: LogFormat::Text,
+            max_file_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+            tracing: None,
+        }
+    }
+}
+
+/// Where spans following each request through the actor are exported
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TracingConfig {
+    /// OTLP/HTTP endpoint of the collector
+    pub otlp_endpoint: String,
+    /// Service name spans are reported under, the actor name by default
+    pub service_name: Option<String>,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: "http://localhost:4318/v1/traces".to_string(),
+            service_name: None,
+        }
+    }
+}
+
+/// Metrics that cost more to collect, and so are only reported when enabled
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Meter the fuel each call into the component consumes. Metering slows
+    /// down all WASM execution.
+    pub fuel: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MailboxConfig {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+    /// Status returned to HTTP callers whose request is rejected or dropped
+    pub reject_status: u16,
+    /// How long shutdown waits for queued messages to be processed
+    pub drain_timeout_ms: u64,
+}
+
+/// What happens to a new message when the mailbox is full
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverflowPolicy {
+    Block,
+    Reject,
+    DropOldest,
+}
+
+impl Default for MailboxConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 32,
+            overflow: OverflowPolicy::Block,
+            reject_status: 503,
+            drain_timeout_ms: 5000,
         }
     }
 }
@@ -58,17 +152,379 @@ pub enum HandlerConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HttpHandlerConfig {
     pub port: u16,
+    /// Host or IP address to listen on, or `unix:<path>` for a unix domain socket
+    #[serde(default = "default_bind_address")]
+    pub address: String,
+    /// Serve only requests under this path, sharing the address with other
+    /// handlers; the prefix is removed before routing
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+    /// Serve HTTPS instead of plain HTTP
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Credentials required to post messages; open to anyone when omitted
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+}
+
+/// How a handler authenticates callers before their request reaches the actor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum AuthConfig {
+    /// `Authorization: Bearer <token>` matching one of the configured tokens
+    Bearer { tokens: Vec<BearerToken> },
+    /// Requests signed with HMAC-SHA256 using a shared secret
+    Hmac {
+        keys: Vec<HmacKey>,
+        /// How far the signed timestamp may be from the current time
+        #[serde(default = "default_hmac_max_skew_secs")]
+        max_skew_secs: u64,
+    },
+    /// `Authorization: Bearer <jwt>` verified against a local JWKS file
+    Jwt {
+        jwks_path: PathBuf,
+        #[serde(default)]
+        issuer: Option<String>,
+        #[serde(default)]
+        audience: Option<String>,
+        /// Algorithms accepted for keys whose JWK has no `alg`
+        #[serde(default)]
+        algorithms: Vec<jsonwebtoken::Algorithm>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BearerToken {
+    pub token: String,
+    pub principal: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HmacKey {
+    pub key_id: String,
+    pub secret: String,
+    /// Principal for requests signed with this key, defaulting to `key_id`
+    #[serde(default)]
+    pub principal: Option<String>,
+}
+
+fn default_hmac_max_skew_secs() -> u64 {
+    300
+}
+
+pub const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1";
+
+fn default_bind_address() -> String {
+    DEFAULT_BIND_ADDRESS.to_string()
+}
+
+/// Where a listener accepts connections
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BindAddress {
+    Tcp(SocketAddr),
+    /// A unix domain socket at this path
+    Unix(PathBuf),
+}
+
+impl BindAddress {
+    /// Parses a host name, an IPv4 or IPv6 address (brackets optional) or
+    /// `unix:<path>`; TCP addresses listen on `port`
+    pub fn parse(address: &str, port: u16) -> anyhow::Result<Self> {
+        if let Some(path) = address.strip_prefix("unix:") {
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+        let host = address.trim_start_matches('[').trim_end_matches(']');
+        (host, port)
+            .to_socket_addrs()
+            .map_err(|e| anyhow!("Invalid bind address {}: {}", address, e))?
+            .next()
+            .map(Self::Tcp)
+            .ok_or_else(|| anyhow!("Bind address {} did not resolve", address))
+    }
+}
+
+impl fmt::Display for BindAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{}", addr),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Certificates a handler terminates TLS with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain presented to clients, leaf first
+    pub cert_path: PathBuf,
+    /// PEM private key for the leaf certificate
+    pub key_path: PathBuf,
+    /// PEM roots that client certificates must chain to; when set, clients
+    /// without a valid certificate are refused during the handshake
+    #[serde(default)]
+    pub client_ca_path: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HttpServerHandlerConfig {
     pub port: u16,
+    /// Host or IP address to listen on, or `unix:<path>` for a unix domain socket
+    #[serde(default = "default_bind_address")]
+    pub address: String,
+    /// Serve only requests under this path, sharing the address with other
+    /// handlers; the prefix is removed before routing
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+    /// Serve HTTPS instead of plain HTTP
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// How long a request waits for the actor before failing with 504
+    #[serde(default = "default_request_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Larger request bodies are rejected with 413; 10 MiB when omitted, and
+    /// unlimited when null
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: Option<u64>,
+    /// Hand request bodies to the actor unread, for it to pull in chunks
+    /// through the `request-body` resource, instead of buffering them
+    #[serde(default)]
+    pub stream_body: bool,
+    /// Overrides `timeout_ms` for requests under a path prefix
+    #[serde(default)]
+    pub route_timeouts: Vec<RouteTimeout>,
+    /// Requests forwarded to the actor; when empty, every request is forwarded
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    /// Stages applied to requests before they reach the mailbox, in order
+    #[serde(default)]
+    pub middleware: Vec<MiddlewareConfig>,
+    /// Credentials required to call the actor; open to anyone when omitted
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+    /// Files answered directly, without authentication; requests for anything
+    /// else go to the actor
+    #[serde(default)]
+    pub static_files: Option<StaticFilesConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StaticFilesConfig {
+    /// Directory whose files are served at the matching request paths
+    pub dir: PathBuf,
+    /// File served for requests naming a directory
+    #[serde(default = "default_index_file")]
+    pub index: String,
+}
+
+fn default_index_file() -> String {
+    "index.html".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum MiddlewareConfig {
+    /// Answers preflight requests and adds CORS headers; no origins means any origin
+    Cors {
+        #[serde(default)]
+        allow_origins: Vec<String>,
+        #[serde(default = "default_cors_methods")]
+        allow_methods: String,
+        #[serde(default = "default_cors_headers")]
+        allow_headers: String,
+        #[serde(default = "default_cors_max_age_secs")]
+        max_age_secs: u64,
+    },
+    /// Logs method, path, client address, status and latency of each request
+    Logging,
+    /// Rejects larger request bodies with 413
+    BodyLimit { max_bytes: usize },
+    /// Compresses responses with brotli or gzip, following `Accept-Encoding`
+    Compression {
+        #[serde(default = "default_compression_min_bytes")]
+        min_bytes: usize,
+    },
+    /// Gives requests without one an id header, echoed on the response
+    RequestId {
+        #[serde(default = "default_request_id_header")]
+        header: String,
+    },
+}
+
+fn default_cors_methods() -> String {
+    "GET, POST, PUT, PATCH, DELETE, OPTIONS".to_string()
+}
+
+fn default_cors_headers() -> String {
+    "*".to_string()
+}
+
+fn default_cors_max_age_secs() -> u64 {
+    86400
+}
+
+fn default_compression_min_bytes() -> usize {
+    1024
+}
+
+fn default_request_id_header() -> String {
+    "X-Request-Id".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteTimeout {
+    pub path: String,
+    pub timeout_ms: u64,
+}
+
+/// A request pattern the `Http-server` handler forwards to the actor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Route {
+    /// Method to match, or any method when omitted
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Path pattern where `:name` captures a segment and `*name` captures the rest
+    pub path: String,
+    /// Passed to the actor with matching requests so it can dispatch on it
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+impl Route {
+    /// Path parameters captured from `path`, if it matches this route's pattern
+    pub fn match_path(&self, path: &str) -> Option<Vec<(String, String)>> {
+        let mut params = Vec::new();
+        let mut segments = path.split('/');
+
+        for pattern in self.path.split('/') {
+            if let Some(name) = pattern.strip_prefix('*') {
+                let rest: Vec<&str> = segments.by_ref().collect();
+                params.push((name.to_string(), rest.join("/")));
+                return Some(params);
+            }
+
+            let segment = segments.next()?;
+            match pattern.strip_prefix(':') {
+                Some(name) if !segment.is_empty() => {
+                    params.push((name.to_string(), segment.to_string()))
+                }
+                Some(_) => return None,
+                None if pattern == segment => {}
+                None => return None,
+            }
+        }
+
+        segments.next().is_none().then_some(params)
+    }
+
+    fn allows(&self, method: &str) -> bool {
+        self.method
+            .as_ref()
+            .is_none_or(|allowed| allowed.eq_ignore_ascii_case(method))
+    }
+}
+
+/// Outcome of matching a request against the configured routes
+#[derive(Debug)]
+pub enum RouteMatch<'a> {
+    /// No routes are configured, so the actor handles every request
+    Unrouted,
+    Matched {
+        route: &'a Route,
+        params: Vec<(String, String)>,
+    },
+    /// The path matched, but only for these methods
+    MethodNotAllowed(Vec<String>),
+    NotFound,
+}
+
+pub const DEFAULT_REQUEST_TIMEOUT_MS: u64 = 30_000;
+
+fn default_request_timeout_ms() -> u64 {
+    DEFAULT_REQUEST_TIMEOUT_MS
+}
+
+/// Largest request body a handler reads unless configured otherwise
+pub const DEFAULT_MAX_BODY_BYTES: u64 = 10 * 1024 * 1024;
+
+fn default_max_body_bytes() -> Option<u64> {
+    Some(DEFAULT_MAX_BODY_BYTES)
+}
+
+impl HttpHandlerConfig {
+    pub fn bind_address(&self) -> anyhow::Result<BindAddress> {
+        BindAddress::parse(&self.address, self.port)
+    }
+}
+
+impl Default for HttpServerHandlerConfig {
+    fn default() -> Self {
+        Self {
+            port: 0,
+            address: DEFAULT_BIND_ADDRESS.to_string(),
+            path_prefix: None,
+            tls: None,
+            timeout_ms: DEFAULT_REQUEST_TIMEOUT_MS,
+            max_body_bytes: Some(DEFAULT_MAX_BODY_BYTES),
+            stream_body: false,
+            route_timeouts: Vec::new(),
+            routes: Vec::new(),
+            middleware: Vec::new(),
+            auth: None,
+            static_files: None,
+        }
+    }
+}
+
+impl HttpServerHandlerConfig {
+    pub fn bind_address(&self) -> anyhow::Result<BindAddress> {
+        BindAddress::parse(&self.address, self.port)
+    }
+
+    /// Timeout for a request path, preferring the longest route prefix it is
+    /// under. Prefixes match whole segments, so `/api` does not cover `/apiv2`.
+    pub fn timeout_for(&self, path: &str) -> u64 {
+        self.route_timeouts
+            .iter()
+            .filter(|route| listener::is_under(path, route.path.trim_end_matches('/')))
+            .max_by_key(|route| route.path.len())
+            .map(|route| route.timeout_ms)
+            .unwrap_or(self.timeout_ms)
+    }
+
+    /// First route matching the request, in the order routes are declared
+    pub fn route_for(&self, method: &str, path: &str) -> RouteMatch<'_> {
+        if self.routes.is_empty() {
+            return RouteMatch::Unrouted;
+        }
+
+        let mut allowed = Vec::new();
+        for route in &self.routes {
+            let Some(params) = route.match_path(path) else {
+                continue;
+            };
+            if route.allows(method) {
+                return RouteMatch::Matched { route, params };
+            }
+            allowed.extend(route.method.iter().map(|m| m.to_ascii_uppercase()));
+        }
+
+        if allowed.is_empty() {
+            RouteMatch::NotFound
+        } else {
+            allowed.sort();
+            allowed.dedup();
+            RouteMatch::MethodNotAllowed(allowed)
+        }
+    }
 }
 
 impl ManifestConfig {
     pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Self> {
         let content = std::fs::read_to_string(path)?;
         let config: ManifestConfig = toml::from_str(&content)?;
+        if config.mailbox.capacity == 0 {
+            anyhow::bail!("Mailbox capacity must be at least 1");
+        }
         Ok(config)
     }
 
@@ -0,0 +1,139 @@
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::StatusCode;
+use percent_encoding::percent_decode_str;
+use serde_json::json;
+
+use super::{error_response, json_response, status_response, EventQuery, NEXT_CURSOR_HEADER};
+use crate::chain::HashChain;
+use crate::listener::{Request, Response};
+use crate::registry::{InspectError, ACTOR_REGISTRY};
+
+/// Answers `GET /actors/{name}/...` from the named actor's own chain:
+///
+/// - `chain/head`: the head hash and number of entries
+/// - `chain`: entries newest first, paged with `limit` and `cursor`
+/// - `chain/{hash}`: a single entry
+/// - `state`: the current state
+/// - `state/{hash}`: the state as of an entry
+pub async fn route(req: &Request, path: &str) -> Response {
+    let mut segments = path.split('/');
+    let name = match segments.next().map(percent_decode_str) {
+        Some(name) => name.decode_utf8_lossy().into_owned(),
+        None => return status_response(StatusCode::NOT_FOUND),
+    };
+    let resource = segments.next();
+    let hash = segments.next();
+    if segments.next().is_some() {
+        return status_response(StatusCode::NOT_FOUND);
+    }
+
+    let result = match (resource, hash) {
+        (Some("chain"), Some("head")) => {
+            inspect(&name, |chain| {
+                json_response(&json!({
+                    "head": chain.get_head(),
+                    "length": chain.len(),
+                }))
+            })
+            .await
+        }
+        (Some("chain"), Some(hash)) => {
+            let hash = hash.to_string();
+            inspect(&name, move |chain| match chain.get_entry(&hash) {
+                Some(entry) => json_response(&json!({
+                    "hash": hash,
+                    "parent": entry.parent,
+                    "event": entry.event,
+                })),
+                None => unknown_entry(&hash),
+            })
+            .await
+        }
+        (Some("chain"), None) => {
+            let query = match EventQuery::parse(req.uri().query()) {
+                Ok(query) => query,
+                Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+            };
+            inspect(&name, move |chain| page(chain, query)).await
+        }
+        (Some("state"), Some(hash)) => {
+            let hash = hash.to_string();
+            inspect(&name, move |chain| match chain.get_entry(&hash) {
+                Some(_) => json_response(&json!({
+                    "hash": hash,
+                    "state": chain.get_state_at(&hash),
+                })),
+                None => unknown_entry(&hash),
+            })
+            .await
+        }
+        (Some("state"), None) => {
+            inspect(&name, |chain| {
+                json_response(&json!({
+                    "head": chain.get_head(),
+                    "state": chain.get_current_state(),
+                }))
+            })
+            .await
+        }
+        _ => return status_response(StatusCode::NOT_FOUND),
+    };
+
+    result.unwrap_or_else(|e| {
+        let status = match e {
+            InspectError::UnknownActor(_) => StatusCode::NOT_FOUND,
+            InspectError::NotRunning(_) | InspectError::Timeout(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+        };
+        error_response(status, &e.to_string())
+    })
+}
+
+async fn inspect<F>(name: &str, respond: F) -> Result<Response, InspectError>
+where
+    F: FnOnce(&HashChain) -> Response + Send + 'static,
+{
+    ACTOR_REGISTRY.inspect_chain(name, respond).await
+}
+
+/// Entries newest first, starting after the `cursor` entry when one is given
+fn page(chain: &HashChain, query: EventQuery) -> Response {
+    let start = match &query.cursor {
+        Some(cursor) => match chain.get_entry(cursor) {
+            Some(entry) => entry.parent.as_deref(),
+            None => return unknown_entry(cursor),
+        },
+        None => chain.get_head(),
+    };
+
+    let mut entries = chain.entries_from(start);
+    let page: Vec<_> = entries
+        .by_ref()
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    let mut response = json_response(&json!({
+        "head": chain.get_head(),
+        "entries": page,
+    }));
+    // Clients pass the last hash of a page as the cursor for the next one
+    if entries.next().is_some() {
+        if let (Some((last, _)), Ok(name)) = (
+            page.last(),
+            HeaderName::from_bytes(NEXT_CURSOR_HEADER.as_bytes()),
+        ) {
+            if let Ok(value) = HeaderValue::from_str(last) {
+                response.headers_mut().insert(name, value);
+            }
+        }
+    }
+    response
+}
+
+fn unknown_entry(hash: &str) -> Response {
+    error_response(
+        StatusCode::NOT_FOUND,
+        &format!("No chain entry with hash {}", hash),
+    )
+}
@@ -0,0 +1,69 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::logging::{ChainEvent, ChainEventType};
+
+/// Narrows the events a client receives; an empty filter matches everything
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct EventFilter {
+    /// Actors whose events match, or every actor when empty
+    pub actors: Vec<String>,
+    /// Event types that match, or every type when empty
+    pub event_types: Vec<ChainEventType>,
+    /// Only events at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only events before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Only events whose hash starts with this
+    pub hash_prefix: Option<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &ChainEvent) -> bool {
+        (self.actors.is_empty() || self.actors.contains(&event.actor_name))
+            && (self.event_types.is_empty() || self.event_types.contains(&event.event_type))
+            && self.since.is_none_or(|since| event.timestamp >= since)
+            && self.until.is_none_or(|until| event.timestamp < until)
+            && self
+                .hash_prefix
+                .as_ref()
+                .is_none_or(|prefix| event.hash.starts_with(prefix.as_str()))
+    }
+
+    /// Applies one query parameter to the filter, returning false for
+    /// parameters that are not part of a filter. `actor` and `event_type` can
+    /// be repeated or hold comma-separated lists.
+    pub fn apply_param(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "actor" => self.actors.extend(list(value).map(str::to_string)),
+            "event_type" => {
+                for name in list(value) {
+                    let event_type = serde_json::from_value(Value::String(name.to_string()))
+                        .map_err(|_| anyhow!("Unknown event type '{}'", name))?;
+                    self.event_types.push(event_type);
+                }
+            }
+            "since" => self.since = Some(timestamp(key, value)?),
+            "until" => self.until = Some(timestamp(key, value)?),
+            "hash_prefix" => self.hash_prefix = Some(value.to_string()),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+fn list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+fn timestamp(key: &str, value: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| anyhow!("Invalid {} timestamp '{}': {}", key, value, e))
+}
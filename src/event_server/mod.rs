@@ -1,60 +1,327 @@
 use crate::chain_emitter::CHAIN_EMITTER;
+use crate::listener::{self, Request, Response, Service};
 use crate::logging::ChainEvent;
+use crate::metrics::METRICS;
+use anyhow::{anyhow, Result};
 use futures::SinkExt;
-use futures::{FutureExt, StreamExt};
-use std::convert::Infallible;
-use tokio::sync::broadcast;
-use warp::{ws::Message, Filter, Reply};
-
-pub async fn run_event_server(port: u16) {
-    // Route for getting event history
-    let history = warp::path!("events" / "history").and(warp::get()).map(|| {
-        let history = CHAIN_EMITTER.get_history();
-        warp::reply::json(&history)
-    });
+use futures::StreamExt;
+use hyper::header::{self, HeaderName, HeaderValue};
+use hyper::upgrade::Upgraded;
+use hyper::{Body, Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::future::Future;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+use tracing::{debug, warn};
 
-    // Route for WebSocket connections
-    let ws = warp::path!("events" / "ws")
-        .and(warp::ws())
-        .map(|ws: warp::ws::Ws| ws.on_upgrade(handle_ws_client));
+mod chains;
+mod filter;
 
-    // CORS configuration
-    let cors = warp::cors()
-        .allow_any_origin()
-        .allow_methods(vec!["GET", "POST", "OPTIONS"])
-        .allow_headers(vec!["content-type"]);
+pub use filter::EventFilter;
 
-    // Combine routes
-    let routes = history.or(ws).with(cors).with(warp::trace::request());
+/// Response header with the cursor for the next page of event history
+pub const NEXT_CURSOR_HEADER: &str = "X-Next-Cursor";
 
-    // Start the server
-    println!("Event server listening on port {}", port);
-    warp::serve(routes).run(([127, 0, 0, 1], port)).await;
-}
+/// Event history and live updates, for serving on a listener or mounting on
+/// an address shared with handlers
+pub fn service() -> Service {
+    listener::service(|req: Request| async move {
+        debug!("[EVENTS] {} {}", req.method(), req.uri().path());
+        let origin = req.headers().get(header::ORIGIN).cloned();
 
-async fn handle_ws_client(ws: warp::ws::WebSocket) {
-    let (mut ws_tx, mut ws_rx) = ws.split();
+        let mut response = match (req.method(), req.uri().path()) {
+            // CORS preflight
+            (&Method::OPTIONS, _) => {
+                let mut response = status_response(StatusCode::OK);
+                let headers = response.headers_mut();
+                headers.insert(
+                    header::ACCESS_CONTROL_ALLOW_METHODS,
+                    HeaderValue::from_static("GET, POST, OPTIONS"),
+                );
+                headers.insert(
+                    header::ACCESS_CONTROL_ALLOW_HEADERS,
+                    HeaderValue::from_static("content-type"),
+                );
+                response
+            }
+            // Route for getting event history
+            (&Method::GET, "/events/history") => history(&req),
+            // Route for WebSocket connections
+            (&Method::GET, "/events/ws") => match EventQuery::parse(req.uri().query()) {
+                Ok(query) => websocket(req, move |ws| {
+                    handle_ws_client(ws, query.filter, query.cursor)
+                }),
+                Err(e) => error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+            },
+            (&Method::GET, "/metrics") => metrics(),
+            // Routes for inspecting a single actor's chain
+            (&Method::GET, path) if path.starts_with("/actors/") => {
+                chains::route(&req, &path["/actors/".len()..]).await
+            }
+            _ => status_response(StatusCode::NOT_FOUND),
+        };
 
-    // Subscribe to chain events
-    let mut event_rx = CHAIN_EMITTER.subscribe();
+        // Any origin may read events
+        if let Some(origin) = origin {
+            response
+                .headers_mut()
+                .insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        }
+        response
+    })
+}
 
-    // Forward events to WebSocket clients
-    let mut send_task = tokio::spawn(async move {
-        while let Ok(event) = event_rx.recv().await {
-            if let Ok(json) = serde_json::to_string(&event) {
-                if ws_tx.send(Message::text(json)).await.is_err() {
-                    break;
+/// Query parameters accepted by the history and WebSocket routes
+struct EventQuery {
+    filter: EventFilter,
+    /// Most events to return in one page of history
+    limit: Option<usize>,
+    /// Hash of the last event the client has already seen
+    cursor: Option<String>,
+}
+
+impl EventQuery {
+    fn parse(query: Option<&str>) -> Result<Self> {
+        let mut parsed = Self {
+            filter: EventFilter::default(),
+            limit: None,
+            cursor: None,
+        };
+        for (key, value) in url::form_urlencoded::parse(query.unwrap_or("").as_bytes()) {
+            if parsed.filter.apply_param(&key, &value)? {
+                continue;
+            }
+            match key.as_ref() {
+                "limit" => {
+                    let limit = value
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid limit '{}'", value))?;
+                    parsed.limit = Some(limit);
                 }
+                "cursor" => parsed.cursor = Some(value.into_owned()),
+                _ => {}
             }
         }
-    });
+        Ok(parsed)
+    }
+}
+
+/// Stored events matching the request's filter, oldest first, one page at a time
+fn history(req: &Request) -> Response {
+    let query = match EventQuery::parse(req.uri().query()) {
+        Ok(query) => query,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let events = match &query.cursor {
+        Some(cursor) => match CHAIN_EMITTER.history_after(cursor) {
+            Some(events) => events,
+            None => {
+                let message = format!("Event {} is no longer in the history", cursor);
+                return error_response(StatusCode::GONE, &message);
+            }
+        },
+        None => CHAIN_EMITTER.get_history(),
+    };
+
+    let mut matching = events
+        .into_iter()
+        .filter(|event| query.filter.matches(event));
+    let page: Vec<_> = matching
+        .by_ref()
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
 
-    // Keep the connection alive until client disconnects
-    tokio::select! {
-        _ = (&mut send_task) => {},
-        _ = ws_rx.next() => {
-            send_task.abort();
+    let mut response = json_response(&page);
+    // Clients pass the last hash of a page as the cursor for the next one
+    if matching.next().is_some() {
+        if let (Some(last), Ok(name)) = (
+            page.last(),
+            HeaderName::from_bytes(NEXT_CURSOR_HEADER.as_bytes()),
+        ) {
+            if let Ok(value) = HeaderValue::from_str(&last.hash) {
+                response.headers_mut().insert(name, value);
+            }
         }
+    }
+    response
+}
+
+/// Runtime metrics for Prometheus to scrape
+fn metrics() -> Response {
+    let mut response = Response::new(Body::from(METRICS.render()));
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; version=0.0.4"),
+    );
+    response
+}
+
+fn status_response(status: StatusCode) -> Response {
+    let mut response = Response::default();
+    *response.status_mut() = status;
+    response
+}
+
+fn json_response<T: Serialize>(value: &T) -> Response {
+    match serde_json::to_vec(value) {
+        Ok(body) => {
+            let mut response = Response::new(Body::from(body));
+            response.headers_mut().insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            );
+            response
+        }
+        Err(_) => status_response(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    let mut response = json_response(&json!({ "error": message }));
+    *response.status_mut() = status;
+    response
+}
+
+/// Accepts a WebSocket handshake and hands the connection to `handler`
+fn websocket<F, Fut>(mut req: Request, handler: F) -> Response
+where
+    F: FnOnce(WebSocketStream<Upgraded>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let is_upgrade = req
+        .headers()
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    let Some(key) = req.headers().get(header::SEC_WEBSOCKET_KEY) else {
+        return status_response(StatusCode::BAD_REQUEST);
+    };
+    if !is_upgrade {
+        return status_response(StatusCode::BAD_REQUEST);
+    }
+    let Ok(accept) = HeaderValue::from_str(&derive_accept_key(key.as_bytes())) else {
+        return status_response(StatusCode::BAD_REQUEST);
     };
+
+    let upgrade = hyper::upgrade::on(&mut req);
+    tokio::spawn(async move {
+        match upgrade.await {
+            Ok(upgraded) => {
+                handler(WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await).await
+            }
+            Err(e) => warn!("[EVENTS] WebSocket upgrade failed: {}", e),
+        }
+    });
+
+    let mut response = status_response(StatusCode::SWITCHING_PROTOCOLS);
+    let headers = response.headers_mut();
+    headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+    headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+    headers.insert(header::SEC_WEBSOCKET_ACCEPT, accept);
+    response
+}
+
+/// Messages a WebSocket client sends to control what it receives
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum ClientMessage {
+    /// Receive only events matching the filter from now on
+    Subscribe {
+        #[serde(flatten)]
+        filter: EventFilter,
+    },
+    /// Replay stored events after `from` that match the filter, then keep
+    /// streaming matching events
+    Resume {
+        from: String,
+        #[serde(flatten)]
+        filter: EventFilter,
+    },
 }
 
+async fn handle_ws_client(
+    ws: WebSocketStream<Upgraded>,
+    mut filter: EventFilter,
+    resume: Option<String>,
+) {
+    let (mut ws_tx, mut ws_rx) = ws.split();
+
+    // Subscribe to chain events, after replaying any the client missed
+    let (mut event_rx, mut replies) = match resume {
+        Some(from) => replay(&from, &filter),
+        None => (CHAIN_EMITTER.subscribe(), Vec::new()),
+    };
+
+    // Forward matching events until the client disconnects
+    loop {
+        for reply in replies.drain(..) {
+            if ws_tx.send(Message::text(reply)).await.is_err() {
+                return;
+            }
+        }
+
+        tokio::select! {
+            event = event_rx.recv() => match event {
+                Ok(event) if filter.matches(&event) => {
+                    if let Ok(json) = serde_json::to_string(&event) {
+                        replies.push(json);
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("[EVENTS] WebSocket client fell behind by {} events", missed);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            message = ws_rx.next() => match message {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                    Ok(ClientMessage::Subscribe { filter: subscribed }) => {
+                        filter = subscribed;
+                        replies.push(json!({ "type": "subscribed" }).to_string());
+                    }
+                    Ok(ClientMessage::Resume { from, filter: resumed }) => {
+                        filter = resumed;
+                        (event_rx, replies) = replay(&from, &filter);
+                    }
+                    Err(e) => replies.push(
+                        json!({ "type": "error", "message": e.to_string() }).to_string(),
+                    ),
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Pings are answered by the WebSocket stream itself
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// A subscription to events emitted from now on, and the messages that
+/// replay stored events after `from`. A `gap` message leads the replay when
+/// `from` has aged out of the history, and a `resumed` message ends it.
+fn replay(from: &str, filter: &EventFilter) -> (broadcast::Receiver<ChainEvent>, Vec<String>) {
+    let replay = CHAIN_EMITTER.resume_after(from);
+    let mut replies = Vec::new();
+    if !replay.complete {
+        replies.push(
+            json!({
+                "type": "gap",
+                "from": from,
+                "oldest": replay.events.first().map(|event| &event.hash),
+            })
+            .to_string(),
+        );
+    }
+    let mut replayed = 0;
+    for event in replay.events.iter().filter(|event| filter.matches(event)) {
+        if let Ok(json) = serde_json::to_string(event) {
+            replies.push(json);
+            replayed += 1;
+        }
+    }
+    replies.push(json!({ "type": "resumed", "from": from, "replayed": replayed }).to_string());
+    (replay.rx, replies)
+}
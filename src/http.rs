@@ -1,13 +1,22 @@
 use anyhow::{anyhow, Result};
-use reqwest::Client;
-use serde_json::Value;
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Method, StatusCode};
+use reqwest::{Certificate, Client, Identity};
+use serde_json::{json, Value};
 use std::future::Future;
 use std::pin::Pin;
-use tide::{Request, Response, Server};
-use tokio::sync::mpsc;
-use tracing::{error, info};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tracing::{info, warn, Span};
 
-use crate::{ActorInput, ActorMessage, HostHandler};
+use crate::auth::{AuthError, AuthRequest, Authenticator};
+use crate::config::{HttpHandlerConfig, TlsConfig, DEFAULT_BIND_ADDRESS, DEFAULT_MAX_BODY_BYTES};
+use crate::http_server::{read_body, request_headers, request_target, ReadBodyError};
+use crate::listener::{self, Mount, Request, Response};
+use crate::mailbox::{Mailbox, MailboxError};
+use crate::metrics;
+use crate::telemetry;
+use crate::{ActorInput, ActorMessage, HostHandler, MessageMetadata};
 
 // HTTP interface for actor-to-actor communication
 #[derive(Clone)]
@@ -15,24 +24,49 @@ use crate::{ActorInput, ActorMessage, HostHandler};
 pub struct HttpHost {
     client: Client,
     port: u16,
-    mailbox_tx: mpsc::Sender<ActorMessage>,
+    mailbox: Mailbox,
+    auth: Option<Arc<Authenticator>>,
 }
 
 impl HttpHost {
-    pub fn new(mailbox_tx: mpsc::Sender<ActorMessage>) -> Self {
+    pub fn new(mailbox: Mailbox) -> Self {
         Self {
             client: Client::new(),
             port: 0, // Default port
-            mailbox_tx,
+            mailbox,
+            auth: None,
         }
     }
 
+    /// Require callers to authenticate before their messages are delivered
+    pub fn with_auth(mut self, auth: Arc<Authenticator>) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Present the handler's certificate when sending to other actors, trusting
+    /// peers whose certificates chain to its client CA
+    pub fn with_tls(mut self, tls: &TlsConfig) -> Result<Self> {
+        let cert = std::fs::read(&tls.cert_path)?;
+        // native-tls only accepts PKCS#8 keys for client identities
+        let key = std::fs::read(&tls.key_path)?;
+        let mut builder = Client::builder().identity(Identity::from_pkcs8_pem(&cert, &key)?);
+        if let Some(ca_path) = &tls.client_ca_path {
+            for root in Certificate::from_pem_bundle(&std::fs::read(ca_path)?)? {
+                builder = builder.add_root_certificate(root);
+            }
+        }
+        self.client = builder.build()?;
+        Ok(self)
+    }
+
     // Send a message to another actor
     pub async fn send_message(&self, address: String, message: Value) -> Result<()> {
         info!("[HTTP] Sending message to {}", address);
         // Fire and forget POST request
         self.client
             .post(address)
+            .headers(telemetry::trace_headers(&Span::current()))
             .json(&message)
             .send()
             .await
@@ -42,41 +76,152 @@ impl HttpHost {
     }
 
     // Handle incoming message
-    async fn handle_request(mut req: Request<mpsc::Sender<ActorMessage>>) -> tide::Result {
-        match req.method() {
-            tide::http::Method::Post => {
-                // Get JSON payload
-                let payload: Value = req.body_json().await?;
-
-                info!("[HTTP] Received message");
-
-                // Create message with no response channel
-                let msg = ActorMessage {
-                    content: ActorInput::Message(payload),
-                    metadata: None,
-                };
-
-                // Send to actor
-                req.state()
-                    .send(msg)
-                    .await
-                    .map_err(|_| tide::Error::from_str(500, "Failed to forward message"))?;
-
-                // Simple OK response
-                Ok(Response::new(200))
+    async fn handle_request(self, mut req: Request) -> Response {
+        if req.uri().path() != "/" {
+            return status_response(StatusCode::NOT_FOUND);
+        }
+        if req.method() != Method::POST {
+            return status_response(StatusCode::METHOD_NOT_ALLOWED);
+        }
+
+        // Credentials in the headers are checked before reading the body. HMAC
+        // signatures cover the body, so it is read first for them.
+        let headers = request_headers(&req);
+        let needs_body = self.auth.as_ref().is_some_and(|auth| auth.needs_body());
+        let mut principal = None;
+        if !needs_body {
+            principal = match self.authenticate(&req, &headers, &[]) {
+                Ok(principal) => principal,
+                Err(e) => return self.unauthorized(e),
+            };
+        }
+
+        let body = std::mem::take(req.body_mut());
+        let body = match read_body(body, Some(DEFAULT_MAX_BODY_BYTES)).await {
+            Ok(body) => body,
+            Err(ReadBodyError::TooLarge) => {
+                return error_response(StatusCode::PAYLOAD_TOO_LARGE, "Message too large")
             }
-            _ => Ok(Response::new(405)), // Method Not Allowed
+            Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+        };
+        if needs_body {
+            principal = match self.authenticate(&req, &headers, &body) {
+                Ok(principal) => principal,
+                Err(e) => return self.unauthorized(e),
+            };
+        }
+
+        // Get JSON payload
+        let payload: Value = match serde_json::from_slice(&body) {
+            Ok(payload) => payload,
+            Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+        };
+
+        info!("[HTTP] Received message");
+
+        // Authenticated messages reach the actor with their sender
+        let (payload, metadata) = match principal {
+            Some(principal) => (
+                json!({ "principal": principal, "message": payload }),
+                Some(MessageMetadata::Authenticated { principal }),
+            ),
+            None => (payload, None),
+        };
+
+        // Create message with no response channel
+        let msg = ActorMessage::new(ActorInput::Message(payload), metadata);
+
+        // Send to actor
+        match self.mailbox.send(msg).await {
+            // Simple OK response
+            Ok(()) => status_response(StatusCode::OK),
+            Err(MailboxError::Full) => status_response(
+                StatusCode::from_u16(self.mailbox.reject_status())
+                    .unwrap_or(StatusCode::SERVICE_UNAVAILABLE),
+            ),
+            Err(MailboxError::Closed) => error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to forward message",
+            ),
         }
     }
+
+    /// The caller's principal, when auth is configured
+    fn authenticate(
+        &self,
+        req: &Request,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) -> Result<Option<String>, AuthError> {
+        let Some(auth) = &self.auth else {
+            return Ok(None);
+        };
+        let target = request_target(req);
+        let request = AuthRequest {
+            method: "POST",
+            target: &target,
+            headers,
+            body,
+        };
+        auth.authenticate(&request).map(Some)
+    }
+
+    fn unauthorized(&self, error: AuthError) -> Response {
+        warn!("[HTTP] Rejected message: {}", error);
+        let mut response = status_response(StatusCode::UNAUTHORIZED);
+        if self.auth.as_ref().is_some_and(|auth| auth.uses_bearer()) {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+fn status_response(status: StatusCode) -> Response {
+    let mut response = Response::default();
+    *response.status_mut() = status;
+    response
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    let mut response = Response::new(Body::from(message.to_string()));
+    *response.status_mut() = status;
+    response
 }
 
 pub struct HttpHandler {
-    port: u16,
+    config: HttpHandlerConfig,
+    shutdown: Arc<Notify>,
+    mount: Mutex<Option<Mount>>,
 }
 
 impl HttpHandler {
     pub fn new(port: u16) -> Self {
-        Self { port }
+        Self::from_config(HttpHandlerConfig {
+            port,
+            address: DEFAULT_BIND_ADDRESS.to_string(),
+            path_prefix: None,
+            tls: None,
+            auth: None,
+        })
+    }
+
+    pub fn from_config(config: HttpHandlerConfig) -> Self {
+        Self {
+            config,
+            shutdown: Arc::new(Notify::new()),
+            mount: Mutex::new(None),
+        }
+    }
+
+    fn claim(&self) -> Result<Mount> {
+        let prefix = self.config.path_prefix.as_deref().unwrap_or("");
+        Mount::bind(
+            &self.config.bind_address()?,
+            self.config.tls.as_ref(),
+            prefix,
+        )
     }
 }
 
@@ -86,43 +231,52 @@ impl HostHandler for HttpHandler {
     }
 
     fn new(config: Value) -> Self {
-        let port = config.get("port").unwrap().as_u64().unwrap() as u16;
-        Self { port }
+        Self::from_config(serde_json::from_value(config).unwrap())
     }
 
-    fn start(
-        &self,
-        mailbox_tx: mpsc::Sender<ActorMessage>,
-    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+    fn start(&self, mailbox: Mailbox) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
         Box::pin(async move {
-            let mut app = Server::with_state(mailbox_tx);
-            app.at("/").post(HttpHost::handle_request);
-
-            // Spawn the server in a separate task
-            let server_port = self.port;
-            tokio::spawn(async move {
-                match app.listen(format!("127.0.0.1:{}", server_port)).await {
-                    Ok(_) => {
-                        info!("[HTTP] HTTP server exited");
-                    }
-                    Err(e) => {
-                        error!("[HTTP] HTTP server failed: {}", e);
-                    }
-                }
-            });
-
-            info!("[HTTP] HTTP server started");
-
-            // Keep this task alive
-            std::future::pending::<()>().await;
+            let mut host = HttpHost::new(mailbox);
+            if let Some(auth) = &self.config.auth {
+                host = host.with_auth(Arc::new(Authenticator::new(auth)?));
+            }
+
+            // Handlers started without `bind` claim their address here
+            let bound = self.mount.lock().unwrap().take();
+            let mount = match bound {
+                Some(mount) => mount,
+                None => self.claim()?,
+            };
+            let address = format!(
+                "{}{}/",
+                self.config.bind_address()?,
+                self.config.path_prefix.as_deref().unwrap_or("")
+            );
+            let service = listener::service(move |req| host.clone().handle_request(req));
+            let service = telemetry::traced(service, "http");
+            mount.serve(metrics::count_responses(service, "http", address.clone()));
+
+            info!("[HTTP] HTTP server started on {}", address);
+
+            // Serve until the handler is stopped
+            self.shutdown.notified().await;
+            drop(mount);
+            info!("[HTTP] HTTP server stopped accepting messages");
 
             Ok(())
         })
     }
 
+    fn bind(&self) -> Result<()> {
+        *self.mount.lock().unwrap() = Some(self.claim()?);
+        Ok(())
+    }
+
     fn stop(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> {
+        let shutdown = self.shutdown.clone();
         Box::pin(async move {
-            // Basic shutdown - the server will stop when dropped
+            // Releases the mount; requests already accepted run to completion
+            shutdown.notify_one();
             Ok(())
         })
     }
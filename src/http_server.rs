@@ -1,94 +1,457 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{self, HeaderName, HeaderValue};
+use hyper::{Body, StatusCode};
 use serde_json::Value;
+use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
-use tide::listener::Listener;
-use tide::{Body, Request, Response, Server};
-use tokio::sync::{mpsc, oneshot};
-use tracing::info;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tokio::sync::{oneshot, Notify};
+use tracing::{info, warn};
+use url::Url;
+use uuid::Uuid;
 
+use crate::auth::{AuthError, AuthRequest, Authenticator};
+use crate::config::{AuthConfig, HttpServerHandlerConfig, RouteMatch};
+use crate::listener::{self, ConnectionInfo, Mount, OriginalUri, Request, Response};
+use crate::mailbox::{Mailbox, MailboxError};
+use crate::metrics;
+use crate::middleware;
+use crate::static_files::StaticFiles;
+use crate::telemetry;
 use crate::{ActorInput, ActorMessage, ActorOutput, HostHandler, MessageMetadata};
 
+/// Response header carrying the id used for this request in the actor's chain
+pub const CORRELATION_ID_HEADER: &str = "X-Correlation-Id";
+
 #[derive(Clone)]
 pub struct HttpServerHost {
-    mailbox_tx: mpsc::Sender<ActorMessage>,
+    mailbox: Mailbox,
+    config: Arc<HttpServerHandlerConfig>,
+    auth: Option<Arc<Authenticator>>,
+    static_files: Option<Arc<StaticFiles>>,
+}
+
+/// Request headers with one entry per value, as a header can be sent more than once
+pub(crate) fn request_headers(req: &Request) -> Vec<(String, String)> {
+    req.headers()
+        .iter()
+        .map(|(k, v)| {
+            let value = String::from_utf8_lossy(v.as_bytes()).into_owned();
+            (k.to_string(), value)
+        })
+        .collect()
+}
+
+/// Path and query string of a request as the client sent it, which HMAC clients sign
+pub(crate) fn request_target(req: &Request) -> String {
+    let uri = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|original| &original.0)
+        .unwrap_or(req.uri());
+    match uri.path_and_query() {
+        Some(target) => target.to_string(),
+        None => uri.path().to_string(),
+    }
+}
+
+/// Failure to read a request body
+#[derive(Error, Debug)]
+pub(crate) enum ReadBodyError {
+    #[error("Request body too large")]
+    TooLarge,
+
+    #[error(transparent)]
+    Failed(#[from] hyper::Error),
+}
+
+/// A whole request body, stopping as soon as it grows past `max_bytes`
+pub(crate) async fn read_body(
+    mut body: Body,
+    max_bytes: Option<u64>,
+) -> Result<Vec<u8>, ReadBodyError> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        bytes.extend_from_slice(&chunk?);
+        if max_bytes.is_some_and(|limit| bytes.len() as u64 > limit) {
+            return Err(ReadBodyError::TooLarge);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Full URL of a request as the client sent it
+pub(crate) fn request_url(req: &Request) -> Option<Url> {
+    let scheme = match req.extensions().get::<ConnectionInfo>() {
+        Some(info) if info.tls => "https",
+        _ => "http",
+    };
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or("localhost");
+    Url::parse(&format!("{}://{}{}", scheme, host, request_target(req))).ok()
 }
 
 impl HttpServerHost {
-    pub fn new(mailbox_tx: mpsc::Sender<ActorMessage>) -> Self {
-        Self { mailbox_tx }
+    pub fn new(
+        mailbox: Mailbox,
+        config: Arc<HttpServerHandlerConfig>,
+        auth: Option<Arc<Authenticator>>,
+    ) -> Self {
+        let static_files = config
+            .static_files
+            .as_ref()
+            .map(|files| Arc::new(StaticFiles::new(files)));
+        Self {
+            mailbox,
+            config,
+            auth,
+            static_files,
+        }
     }
 
-    async fn handle_request(mut req: Request<HttpServerHost>) -> tide::Result {
-        info!("Received {} request to {}", req.method(), req.url().path());
-        
+    async fn handle_request(self, req: Request) -> Response {
+        info!("Received {} request to {}", req.method(), req.uri().path());
+
+        // Files take precedence, so the actor only sees what the directory can't answer
+        let mut req = match &self.static_files {
+            Some(files) => match files.serve(req).await {
+                Ok(response) => return response,
+                Err(req) => req,
+            },
+            None => req,
+        };
+
+        // Identifies this request in error responses and chain entries
+        let correlation_id = Uuid::new_v4().to_string();
+        let path = req.uri().path().to_string();
+        let timeout = Duration::from_millis(self.config.timeout_for(&path));
+
+        // Requests outside the declared routes never reach the actor
+        let (params, route_tag) = match self.config.route_for(req.method().as_str(), &path) {
+            RouteMatch::Unrouted => (Vec::new(), None),
+            RouteMatch::Matched { route, params } => (params, route.tag.clone()),
+            RouteMatch::MethodNotAllowed(allowed) => {
+                let output = ActorOutput::http_error(405, "Method not allowed", &correlation_id);
+                let mut response = Self::into_response(output, &correlation_id);
+                if let Ok(allowed) = HeaderValue::from_str(&allowed.join(", ")) {
+                    response.headers_mut().insert(header::ALLOW, allowed);
+                }
+                return response;
+            }
+            RouteMatch::NotFound => {
+                let output = ActorOutput::http_error(404, "Not found", &correlation_id);
+                return Self::into_response(output, &correlation_id);
+            }
+        };
+
         // Create a channel for receiving the response
         let (response_tx, response_rx) = oneshot::channel();
 
-        // Get the body bytes
-        let body_bytes = req.body_bytes().await?.to_vec();
+        // A declared length over the limit is rejected before reading anything
+        let max_body_bytes = self.config.max_body_bytes;
+        let too_large = |correlation_id: &str| {
+            warn!("Rejected request {}: body too large", correlation_id);
+            let output = ActorOutput::http_error(413, "Request body too large", correlation_id);
+            Self::into_response(output, correlation_id)
+        };
+        let declared = req
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok()?.parse::<u64>().ok());
+        if max_body_bytes.is_some_and(|limit| declared.is_some_and(|len| len > limit)) {
+            return too_large(&correlation_id);
+        }
+
+        // Credentials in the headers are checked before reading the body. HMAC
+        // signatures cover the body, so it is read first for them.
+        let headers = request_headers(&req);
+        let needs_body = self.auth.as_ref().is_some_and(|auth| auth.needs_body());
+        let mut principal = None;
+        if !needs_body {
+            principal = match self.authenticate(&req, &headers, &[]) {
+                Ok(principal) => principal,
+                Err(e) => return self.unauthorized(e, &correlation_id),
+            };
+        }
+
+        // Get the body bytes, unless the actor reads the body itself
+        let body = std::mem::take(req.body_mut());
+        let (body_bytes, body_stream) = if self.config.stream_body {
+            (None, Some(RequestBody::new(body, max_body_bytes)))
+        } else {
+            match read_body(body, max_body_bytes).await {
+                Ok(bytes) => (Some(bytes), None),
+                Err(ReadBodyError::TooLarge) => return too_large(&correlation_id),
+                Err(e) => {
+                    let output = ActorOutput::http_error(400, &e.to_string(), &correlation_id);
+                    return Self::into_response(output, &correlation_id);
+                }
+            }
+        };
+        if needs_body {
+            let body = body_bytes.as_deref().unwrap_or_default();
+            principal = match self.authenticate(&req, &headers, body) {
+                Ok(principal) => principal,
+                Err(e) => return self.unauthorized(e, &correlation_id),
+            };
+        }
 
         // Create actor message with http metadata
-        let msg = ActorMessage {
-            content: ActorInput::HttpRequest {
+        let peer_addr = req
+            .extensions()
+            .get::<ConnectionInfo>()
+            .and_then(|info| info.peer);
+        let msg = ActorMessage::new(
+            ActorInput::HttpRequest {
                 method: req.method().to_string(),
-                uri: req.url().path().to_string(),
-                headers: req
-                    .iter()
-                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                uri: request_url(&req)
+                    .map(|url| url.to_string())
+                    .unwrap_or_else(|| request_target(&req)),
+                path,
+                query: url::form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes())
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
                     .collect(),
-                body: Some(body_bytes),
+                params,
+                headers,
+                body: body_bytes,
+                body_stream,
+                peer_addr: peer_addr.map(|addr| addr.to_string()),
+                version: Some(format!("{:?}", req.version())),
+                route_tag,
+                principal,
             },
-            metadata: Some(MessageMetadata::HttpRequest {
+            Some(MessageMetadata::HttpRequest {
                 response_channel: response_tx,
+                correlation_id: correlation_id.clone(),
             }),
+        );
+
+        // The timeout covers waiting for room in a full mailbox as well as
+        // for the actor's response
+        let exchange = async {
+            self.mailbox.send(msg).await?;
+            Ok(response_rx.await)
+        };
+        let actor_response = match tokio::time::timeout(timeout, exchange).await {
+            Ok(Ok(Ok(output))) => output,
+            Ok(Ok(Err(_))) => ActorOutput::http_error(
+                500,
+                "Failed to receive response from actor",
+                &correlation_id,
+            ),
+            Ok(Err(MailboxError::Full)) => ActorOutput::http_error(
+                self.mailbox.reject_status(),
+                "Actor mailbox is full",
+                &correlation_id,
+            ),
+            Ok(Err(MailboxError::Closed)) => {
+                ActorOutput::http_error(503, "Failed to forward request to actor", &correlation_id)
+            }
+            // Dropping the receiver lets the actor record the response as abandoned
+            Err(_) => {
+                warn!("Request {} timed out after {:?}", correlation_id, timeout);
+                ActorOutput::http_error(504, "Actor did not respond in time", &correlation_id)
+            }
         };
 
-        // Send to actor
-        req.state()
-            .mailbox_tx
-            .send(msg)
-            .await
-            .map_err(|_| tide::Error::from_str(500, "Failed to forward request to actor"))?;
+        Self::into_response(actor_response, &correlation_id)
+    }
 
-        // Wait for response
-        let actor_response = response_rx
-            .await
-            .map_err(|_| tide::Error::from_str(500, "Failed to receive response from actor"))?;
+    /// The caller's principal, when auth is configured
+    fn authenticate(
+        &self,
+        req: &Request,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) -> Result<Option<String>, AuthError> {
+        let Some(auth) = &self.auth else {
+            return Ok(None);
+        };
+        let target = request_target(req);
+        let request = AuthRequest {
+            method: req.method().as_str(),
+            target: &target,
+            headers,
+            body,
+        };
+        auth.authenticate(&request).map(Some)
+    }
+
+    fn unauthorized(&self, error: AuthError, correlation_id: &str) -> Response {
+        warn!("Rejected request {}: {}", correlation_id, error);
+        let output = ActorOutput::http_error(401, &error.to_string(), correlation_id);
+        let mut response = Self::into_response(output, correlation_id);
+        if self.auth.as_ref().is_some_and(|auth| auth.uses_bearer()) {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
 
+    fn into_response(output: ActorOutput, correlation_id: &str) -> Response {
         // Process actor response
-        match actor_response {
+        let mut response = match output {
             ActorOutput::HttpResponse {
                 status,
                 headers,
                 body,
             } => {
-                let mut response = Response::new(status);
+                let has_body = body.is_some();
+                let mut response = Response::new(body.map(Body::from).unwrap_or_default());
+                *response.status_mut() =
+                    StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
 
                 // Add headers
                 for (key, value) in headers {
-                    response.append_header(key.as_str(), value.as_str());
+                    match (
+                        HeaderName::from_bytes(key.as_bytes()),
+                        HeaderValue::from_str(&value),
+                    ) {
+                        (Ok(name), Ok(value)) => {
+                            response.headers_mut().append(name, value);
+                        }
+                        _ => warn!("Dropping invalid response header {}", key),
+                    }
                 }
 
-                // Set body if present
-                if let Some(body_bytes) = body {
-                    response.set_body(Body::from_bytes(body_bytes));
+                // Bodies are binary unless the actor says otherwise
+                if has_body && !response.headers().contains_key(header::CONTENT_TYPE) {
+                    response.headers_mut().insert(
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static("application/octet-stream"),
+                    );
                 }
 
-                Ok(response)
+                response
+            }
+            _ => {
+                let mut response = Response::default();
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                response
             }
-            _ => Ok(Response::new(500)),
+        };
+
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(CORRELATION_ID_HEADER.as_bytes()),
+            HeaderValue::from_str(correlation_id),
+        ) {
+            response.headers_mut().insert(name, value);
         }
+        response
+    }
+}
+
+/// Body of a request handed to the actor unread, which it pulls in chunks
+/// through the `request-body` resource
+#[derive(Clone)]
+pub struct RequestBody {
+    reader: Arc<Mutex<BodyReader>>,
+}
+
+struct BodyReader {
+    body: Body,
+    /// Rest of the last chunk, beyond what the actor asked for
+    pending: Bytes,
+    received: u64,
+    max_bytes: Option<u64>,
+    taken: bool,
+}
+
+impl fmt::Debug for RequestBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestBody").finish_non_exhaustive()
+    }
+}
+
+impl RequestBody {
+    pub fn new(body: Body, max_bytes: Option<u64>) -> Self {
+        Self {
+            reader: Arc::new(Mutex::new(BodyReader {
+                body,
+                pending: Bytes::new(),
+                received: 0,
+                max_bytes,
+                taken: false,
+            })),
+        }
+    }
+
+    /// Claims the body for the actor; only the first call succeeds
+    pub fn take(&self) -> bool {
+        !std::mem::replace(&mut self.reader.lock().unwrap().taken, true)
+    }
+
+    /// Up to `max_bytes` of the body, or `None` once all of it has been read.
+    /// Blocks the calling thread, which must belong to a multi-threaded runtime.
+    pub fn read(&self, max_bytes: usize) -> Result<Option<Vec<u8>>> {
+        let handle = Handle::try_current()?;
+        if handle.runtime_flavor() != RuntimeFlavor::MultiThread {
+            return Err(anyhow!(
+                "Reading a request body needs the multi-threaded runtime"
+            ));
+        }
+        let mut reader = self.reader.lock().unwrap();
+        tokio::task::block_in_place(|| handle.block_on(reader.read(max_bytes.max(1))))
+    }
+}
+
+impl BodyReader {
+    async fn read(&mut self, max_bytes: usize) -> Result<Option<Vec<u8>>> {
+        while self.pending.is_empty() {
+            let Some(chunk) = self.body.data().await else {
+                return Ok(None);
+            };
+            let chunk = chunk?;
+            self.received += chunk.len() as u64;
+            // Content-Length was checked up front, but chunked bodies have none
+            if let Some(limit) = self.max_bytes.filter(|limit| self.received > *limit) {
+                return Err(anyhow!("Request body exceeds {} bytes", limit));
+            }
+            self.pending = chunk;
+        }
+        let len = max_bytes.min(self.pending.len());
+        Ok(Some(self.pending.split_to(len).to_vec()))
     }
 }
 
 pub struct HttpServerHandler {
-    port: u16,
+    config: Arc<HttpServerHandlerConfig>,
+    shutdown: Arc<Notify>,
+    mount: Mutex<Option<Mount>>,
 }
 
 impl HttpServerHandler {
     pub fn new(port: u16) -> Self {
-        Self { port }
+        Self::from_config(HttpServerHandlerConfig {
+            port,
+            ..Default::default()
+        })
+    }
+
+    pub fn from_config(config: HttpServerHandlerConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            shutdown: Arc::new(Notify::new()),
+            mount: Mutex::new(None),
+        }
+    }
+
+    fn claim(&self) -> Result<Mount> {
+        let prefix = self.config.path_prefix.as_deref().unwrap_or("");
+        Mount::bind(
+            &self.config.bind_address()?,
+            self.config.tls.as_ref(),
+            prefix,
+        )
     }
 }
 
@@ -98,35 +461,71 @@ impl HostHandler for HttpServerHandler {
     }
 
     fn new(config: Value) -> Self {
-        let port = config.get("port").unwrap().as_u64().unwrap() as u16;
-        Self { port }
+        Self::from_config(serde_json::from_value(config).unwrap())
     }
 
-    fn start(
-        &self,
-        mailbox_tx: mpsc::Sender<ActorMessage>,
-    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+    fn start(&self, mailbox: Mailbox) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
         Box::pin(async move {
-            let state = HttpServerHost::new(mailbox_tx);
-            let mut app = Server::with_state(state);
-            app.at("/*").all(HttpServerHost::handle_request);
-            app.at("/").all(HttpServerHost::handle_request);
+            if self.config.stream_body && matches!(self.config.auth, Some(AuthConfig::Hmac { .. }))
+            {
+                return Err(anyhow!(
+                    "HMAC authentication signs the request body, so it cannot be used with stream_body"
+                ));
+            }
+            let auth = match &self.config.auth {
+                Some(config) => Some(Arc::new(Authenticator::new(config)?)),
+                None => None,
+            };
+            let host = HttpServerHost::new(mailbox, self.config.clone(), auth);
+            let mut service = listener::service(move |req| host.clone().handle_request(req));
+            // Wrap from the last stage out, so the first declared stage sees requests first
+            for stage in self.config.middleware.iter().rev() {
+                service = middleware::apply(service, stage)?;
+            }
+            let service = telemetry::traced(service, "Http-server");
 
-            // First bind the server
-            let mut listener = app.bind(format!("127.0.0.1:{}", self.port)).await?;
+            // Handlers started without `bind` claim their address here
+            let bound = self.mount.lock().unwrap().take();
+            let mount = match bound {
+                Some(mount) => mount,
+                None => self.claim()?,
+            };
+            let address = format!(
+                "{}{}/",
+                self.config.bind_address()?,
+                self.config.path_prefix.as_deref().unwrap_or("")
+            );
+            mount.serve(metrics::count_responses(
+                service,
+                "Http-server",
+                address.clone(),
+            ));
 
-            info!("HTTP-SERVER starting on port {}", self.port);
+            info!("HTTP-SERVER starting on {}", address);
 
-            // Then start accepting connections
-            listener.accept().await?;
+            // Serve until the handler is stopped
+            self.shutdown.notified().await;
+            drop(mount);
+            info!(
+                "HTTP-SERVER on port {} stopped accepting requests",
+                self.config.port
+            );
 
             Ok(())
         })
     }
 
+    fn bind(&self) -> Result<()> {
+        *self.mount.lock().unwrap() = Some(self.claim()?);
+        Ok(())
+    }
+
     fn stop(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> {
+        let shutdown = self.shutdown.clone();
         Box::pin(async move {
+            // Releases the mount; requests already accepted still get their response
+            shutdown.notify_one();
             Ok(())
         })
     }
-}
\ No newline at end of file
+}
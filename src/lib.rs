@@ -5,9 +5,12 @@ use serde_json::Value;
 use std::future::Future;
 use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{mpsc, oneshot};
-use tracing::{error, info};
+use tracing::{error, info, info_span, warn, Span};
 
+pub mod auth;
 pub mod capabilities;
 pub mod chain;
 pub mod chain_emitter;
@@ -15,25 +18,61 @@ pub mod config;
 pub mod event_server;
 pub mod http;
 pub mod http_server;
+pub mod listener;
 pub mod logging;
+pub mod mailbox;
+pub mod metrics;
+pub mod middleware;
+pub mod registry;
+pub mod static_files;
 mod store;
+pub mod supervisor;
+pub mod telemetry;
+pub mod tls;
 mod wasm;
 
 use chain::{ChainEvent, HashChain};
-use tracing_subscriber::{EnvFilter, FmtSubscriber};
+use chain_emitter::CHAIN_EMITTER;
+use mailbox::{Mailbox, MailboxReceiver};
+use metrics::METRICS;
+use registry::ACTOR_REGISTRY;
+use supervisor::Supervisor;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
 
 pub use config::{HandlerConfig, HttpHandlerConfig, HttpServerHandlerConfig, ManifestConfig};
 pub use store::Store;
 pub use wasm::{WasmActor, WasmError};
 
+// Requests are moved once per message, so boxing them would gain little
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ActorInput {
     Message(Value),
     HttpRequest {
         method: String,
+        /// Full request URI, including the query string
         uri: String,
+        path: String,
+        /// Decoded query parameters in the order they appeared
+        query: Vec<(String, String)>,
+        /// Parameters captured by the matched route's path pattern
+        params: Vec<(String, String)>,
         headers: Vec<(String, String)>,
+        /// Request body, unless the handler streams bodies through `body_stream`
         body: Option<Vec<u8>>,
+        /// Unread body for the actor to pull in chunks; never recorded in the chain
+        #[serde(skip)]
+        body_stream: Option<http_server::RequestBody>,
+        /// Address of the client that sent the request, if known
+        peer_addr: Option<String>,
+        /// Protocol version, such as `HTTP/1.1`
+        version: Option<String>,
+        /// Tag of the matched route, if the handler declares routes
+        route_tag: Option<String>,
+        /// Caller authenticated by the handler, if it requires authentication
+        principal: Option<String>,
     },
 }
 
@@ -47,6 +86,22 @@ pub enum ActorOutput {
     },
 }
 
+impl ActorOutput {
+    /// JSON error response for HTTP callers whose request did not produce output
+    pub fn http_error(status: u16, message: &str, correlation_id: &str) -> Self {
+        let body = serde_json::json!({
+            "error": message,
+            "correlation_id": correlation_id,
+        });
+
+        ActorOutput::HttpResponse {
+            status,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: serde_json::to_vec(&body).ok(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum MessageMetadata {
     ActorSource {
@@ -55,6 +110,12 @@ pub enum MessageMetadata {
     },
     HttpRequest {
         response_channel: oneshot::Sender<ActorOutput>,
+        /// Returned to the caller so failures can be matched to chain entries
+        correlation_id: String,
+    },
+    /// A message from an external caller that a handler authenticated
+    Authenticated {
+        principal: String,
     },
 }
 
@@ -62,6 +123,61 @@ pub enum MessageMetadata {
 pub struct ActorMessage {
     pub content: ActorInput,
     pub metadata: Option<MessageMetadata>,
+    /// Span the message was sent from, which the actor's handling continues
+    pub span: Span,
+}
+
+impl ActorMessage {
+    /// A message sent from the current span
+    pub fn new(content: ActorInput, metadata: Option<MessageMetadata>) -> Self {
+        Self {
+            content,
+            metadata,
+            span: Span::current(),
+        }
+    }
+}
+
+/// Administrative operations, serviced ahead of anything waiting in the mailbox
+pub enum ControlMessage {
+    Stop {
+        response_channel: oneshot::Sender<()>,
+    },
+    Snapshot {
+        response_channel: oneshot::Sender<ActorSnapshot>,
+    },
+    Upgrade {
+        actor: Box<dyn Actor>,
+        component: String,
+        response_channel: oneshot::Sender<Result<()>>,
+    },
+    HealthCheck {
+        response_channel: oneshot::Sender<ActorHealth>,
+    },
+    /// Run `inspect` against the actor's chain, which answers through a
+    /// channel of its own
+    InspectChain {
+        inspect: Box<dyn FnOnce(&HashChain) + Send>,
+    },
+    /// Close the mailbox, process what is already queued until `drain_timeout`
+    /// passes, then stop
+    Shutdown {
+        drain_timeout: Duration,
+        response_channel: oneshot::Sender<()>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActorSnapshot {
+    pub head: Option<String>,
+    pub state: Option<Value>,
+    pub timestamp: chrono::DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActorHealth {
+    pub head: Option<String>,
+    pub state_valid: bool,
 }
 
 pub trait Actor: Send {
@@ -71,19 +187,23 @@ pub trait Actor: Send {
 }
 
 pub struct ActorProcess {
-    mailbox_rx: mpsc::Receiver<ActorMessage>,
+    mailbox_rx: MailboxReceiver,
+    control_rx: Option<mpsc::Receiver<ControlMessage>>,
     chain: HashChain,
     actor: Box<dyn Actor>,
     name: String,
+    supervisor: Option<Supervisor>,
+    /// Set once a stop or shutdown has been requested through `control_rx`
+    stopped: bool,
 }
 
 impl ActorProcess {
     pub fn new(
         name: &String,
         actor: Box<dyn Actor>,
-        mailbox_rx: mpsc::Receiver<ActorMessage>,
+        mailbox_rx: impl Into<MailboxReceiver>,
     ) -> Result<Self> {
-        let mut chain = HashChain::new();
+        let mut chain = HashChain::new(name);
 
         // Initialize with initial state
         let initial_state = actor.init()?;
@@ -94,71 +214,299 @@ impl ActorProcess {
         });
 
         Ok(Self {
-            mailbox_rx,
+            mailbox_rx: mailbox_rx.into(),
+            control_rx: None,
             chain,
             actor,
             name: name.to_string(),
+            supervisor: None,
+            stopped: false,
         })
     }
 
+    /// Commit child lifecycle events recorded by `supervisor` to this actor's chain
+    pub fn with_supervisor(mut self, supervisor: Supervisor) -> Self {
+        self.supervisor = Some(supervisor);
+        self.commit_supervisor_events();
+        self
+    }
+
+    fn commit_supervisor_events(&mut self) {
+        if let Some(supervisor) = &self.supervisor {
+            for event in supervisor.take_events() {
+                self.chain.add_event(event);
+            }
+        }
+    }
+
+    /// Service `control_rx` ahead of the regular mailbox
+    pub fn with_control(mut self, control_rx: mpsc::Receiver<ControlMessage>) -> Self {
+        self.control_rx = Some(control_rx);
+        self
+    }
+
     pub async fn run(&mut self) -> Result<()> {
-        while let Some(msg) = self.mailbox_rx.recv().await {
-            // Record appropriate chain event based on message type
-            match &msg.metadata {
-                Some(MessageMetadata::ActorSource {
-                    source_actor,
-                    source_chain_state,
-                }) => {
-                    self.chain.add_event(ChainEvent::ActorMessage {
-                        source_actor: source_actor.clone(),
-                        source_chain_state: source_chain_state.clone(),
-                        content: match &msg.content {
-                            ActorInput::Message(v) => v.clone(),
-                            _ => serde_json::to_value(&msg.content).unwrap_or_default(),
-                        },
-                        timestamp: Utc::now(),
-                    });
+        loop {
+            tokio::select! {
+                biased;
+
+                Some(control) = recv_control(&mut self.control_rx) => {
+                    if !self.handle_control(control).await {
+                        break;
+                    }
                 }
-                _ => {
-                    self.chain.add_event(ChainEvent::ExternalInput {
-                        input: msg.content.clone(),
-                        timestamp: Utc::now(),
-                    });
+                msg = self.mailbox_rx.recv() => match msg {
+                    Some(msg) => self.handle_message(msg)?,
+                    None => break,
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns false once the actor has been asked to stop
+    async fn handle_control(&mut self, control: ControlMessage) -> bool {
+        match control {
+            ControlMessage::Stop { response_channel } => {
+                info!("[CONTROL] Stopping actor {}", self.name);
+                // Queued requests are answered rather than dropped with the mailbox
+                self.mailbox_rx.close().await;
+                self.mailbox_rx.reject_remaining().await;
+                self.stopped = true;
+                let _ = response_channel.send(());
+                return false;
+            }
+            ControlMessage::Shutdown {
+                drain_timeout,
+                response_channel,
+            } => {
+                self.drain(drain_timeout).await;
+                self.stopped = true;
+                let _ = response_channel.send(());
+                return false;
+            }
+            ControlMessage::Snapshot { response_channel } => {
+                let _ = response_channel.send(ActorSnapshot {
+                    head: self.chain.get_head().map(str::to_string),
+                    state: self.chain.get_current_state(),
+                    timestamp: Utc::now(),
+                });
+            }
+            ControlMessage::Upgrade {
+                actor,
+                component,
+                response_channel,
+            } => {
+                let result = match self.chain.get_current_state() {
+                    Some(state) if actor.verify_state(&state) => {
+                        info!("[CONTROL] Upgrading actor {} to {}", self.name, component);
+                        self.actor = actor;
+                        self.chain.add_event(ChainEvent::ActorUpgraded {
+                            component,
+                            timestamp: Utc::now(),
+                        });
+                        Ok(())
+                    }
+                    _ => Err(anyhow::anyhow!(
+                        "Current state is not valid for component {}",
+                        component
+                    )),
+                };
+                let _ = response_channel.send(result);
+            }
+            ControlMessage::HealthCheck { response_channel } => {
+                let state_valid = self
+                    .chain
+                    .get_current_state()
+                    .map(|state| self.actor.verify_state(&state))
+                    .unwrap_or(false);
+                let _ = response_channel.send(ActorHealth {
+                    head: self.chain.get_head().map(str::to_string),
+                    state_valid,
+                });
+            }
+            ControlMessage::InspectChain { inspect } => inspect(&self.chain),
+        }
+
+        true
+    }
+
+    async fn drain(&mut self, drain_timeout: Duration) {
+        info!("[CONTROL] Draining mailbox for actor {}", self.name);
+        self.mailbox_rx.close().await;
+
+        let deadline = tokio::time::Instant::now() + drain_timeout;
+        let mut drained = 0;
+        loop {
+            match tokio::time::timeout_at(deadline, self.mailbox_rx.recv()).await {
+                Ok(Some(msg)) => {
+                    if let Err(e) = self.handle_message(msg) {
+                        error!("Failed to handle message during shutdown: {}", e);
+                    }
+                    drained += 1;
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    warn!("[CONTROL] Drain deadline passed for actor {}", self.name);
+                    break;
                 }
             }
+        }
 
-            // Get current state from chain
-            let current_state = self
-                .chain
-                .get_current_state()
-                .ok_or_else(|| anyhow::anyhow!("No current state found in chain"))?;
+        // Anything still queued is turned away rather than silently dropped
+        let abandoned = self.mailbox_rx.reject_remaining().await;
 
-            // Process input
-            let (output, new_state) = self.actor.handle_input(msg.content, &current_state)?;
+        self.chain.add_event(ChainEvent::Shutdown {
+            drained,
+            abandoned,
+            timestamp: Utc::now(),
+        });
 
-            // Record state change
-            let state_hash = self.chain.add_event(ChainEvent::StateChange {
-                old_state: current_state,
-                new_state: new_state.clone(),
-                timestamp: Utc::now(),
-            });
+        // Make sure the logged chain commits, including the shutdown, are written
+        logging::flush();
+    }
 
-            // Record output
-            self.chain.add_event(ChainEvent::Output {
-                output: output.clone(),
-                chain_state: state_hash,
-                timestamp: Utc::now(),
-            });
+    fn handle_message(&mut self, msg: ActorMessage) -> Result<()> {
+        let span = info_span!(
+            parent: &msg.span,
+            "actor.handle",
+            actor = %self.name,
+            chain.hash = tracing::field::Empty,
+        );
+        let _entered = span.enter();
+
+        // Record appropriate chain event based on message type
+        match &msg.metadata {
+            Some(MessageMetadata::ActorSource {
+                source_actor,
+                source_chain_state,
+            }) => {
+                self.chain.add_event(ChainEvent::ActorMessage {
+                    source_actor: source_actor.clone(),
+                    source_chain_state: source_chain_state.clone(),
+                    content: match &msg.content {
+                        ActorInput::Message(v) => v.clone(),
+                        _ => serde_json::to_value(&msg.content).unwrap_or_default(),
+                    },
+                    timestamp: Utc::now(),
+                });
+            }
+            metadata => {
+                let principal = match (metadata, &msg.content) {
+                    (Some(MessageMetadata::Authenticated { principal }), _) => {
+                        Some(principal.clone())
+                    }
+                    (_, ActorInput::HttpRequest { principal, .. }) => principal.clone(),
+                    _ => None,
+                };
+                self.chain.add_event(ChainEvent::ExternalInput {
+                    input: msg.content.clone(),
+                    principal,
+                    timestamp: Utc::now(),
+                });
+            }
+        }
+
+        // Get current state from chain
+        let current_state = self
+            .chain
+            .get_current_state()
+            .ok_or_else(|| anyhow::anyhow!("No current state found in chain"))?;
+
+        // Process input
+        let started = std::time::Instant::now();
+        let result = self.actor.handle_input(msg.content, &current_state);
+        let labels = [("actor", self.name.as_str())];
+        METRICS
+            .handler_duration
+            .observe(&labels, started.elapsed().as_secs_f64());
+        let outcome = if result.is_ok() { "ok" } else { "error" };
+        METRICS
+            .messages_handled
+            .inc(&[("actor", self.name.as_str()), ("outcome", outcome)]);
+
+        // Record any children spawned or stopped while handling the input
+        self.commit_supervisor_events();
+
+        let (output, new_state) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                self.handle_error(e, msg.metadata);
+                return Ok(());
+            }
+        };
 
-            // Send response if metadata contains response channel
-            if let Some(MessageMetadata::HttpRequest { response_channel }) = msg.metadata {
-                let _ = response_channel.send(output);
+        // Record state change
+        let state_hash = self.chain.add_event(ChainEvent::StateChange {
+            old_state: current_state,
+            new_state: new_state.clone(),
+            timestamp: Utc::now(),
+        });
+
+        // Record output
+        let output_hash = self.chain.add_event(ChainEvent::Output {
+            output: output.clone(),
+            chain_state: state_hash,
+            timestamp: Utc::now(),
+        });
+        span.record("chain.hash", output_hash.as_str());
+
+        // Send response if metadata contains response channel
+        if let Some(MessageMetadata::HttpRequest {
+            response_channel,
+            correlation_id,
+        }) = msg.metadata
+        {
+            // The caller may have given up (e.g. timed out) while the actor was working
+            if response_channel.send(output).is_err() {
+                warn!(
+                    "Response for request {} was abandoned by the caller",
+                    correlation_id
+                );
+                self.chain.add_event(ChainEvent::ResponseAbandoned {
+                    correlation_id,
+                    output: output_hash,
+                    timestamp: Utc::now(),
+                });
             }
         }
 
         Ok(())
     }
 
+    /// Record a failed input without touching the state, so one bad message
+    /// does not stop the actor
+    fn handle_error(&mut self, error: anyhow::Error, metadata: Option<MessageMetadata>) {
+        error!("Actor {} failed to handle input: {}", self.name, error);
+
+        let correlation_id = match &metadata {
+            Some(MessageMetadata::HttpRequest { correlation_id, .. }) => {
+                Some(correlation_id.clone())
+            }
+            _ => None,
+        };
+
+        let error_hash = self.chain.add_event(ChainEvent::Error {
+            message: error.to_string(),
+            correlation_id,
+            timestamp: Utc::now(),
+        });
+        Span::current().record("chain.hash", error_hash.as_str());
+
+        if let Some(MessageMetadata::HttpRequest {
+            response_channel,
+            correlation_id,
+        }) = metadata
+        {
+            let _ = response_channel.send(ActorOutput::http_error(
+                500,
+                &error.to_string(),
+                &correlation_id,
+            ));
+        }
+    }
+
     pub fn get_chain(&self) -> &HashChain {
         &self.chain
     }
@@ -183,21 +531,44 @@ impl ActorProcess {
     }
 }
 
+async fn recv_control(
+    control_rx: &mut Option<mpsc::Receiver<ControlMessage>>,
+) -> Option<ControlMessage> {
+    match control_rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
 pub trait HostHandler: Send + Sync {
     fn name(&self) -> &str;
     fn new(config: Value) -> Self
     where
         Self: Sized;
+    /// Claims the handler's listening address ahead of `start`, so a failed
+    /// bind is reported when the actor is created
+    fn bind(&self) -> Result<()> {
+        Ok(())
+    }
     fn start(
         &self,
-        mailbox_tx: mpsc::Sender<ActorMessage>,
+        mailbox: Mailbox,
     ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>>;
     fn stop(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send>>;
 }
 
+/// How long health checks, snapshots and chain inspections wait for the actor
+/// to answer
+pub(crate) const CONTROL_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct ActorRuntime {
     pub config: ManifestConfig,
+    mailbox: Mailbox,
+    control_tx: mpsc::Sender<ControlMessage>,
+    store: Store,
+    supervisor: Supervisor,
     process_handle: Option<tokio::task::JoinHandle<()>>,
+    handlers: Vec<Arc<dyn HostHandler>>,
     handler_tasks: Vec<tokio::task::JoinHandle<()>>,
 }
 
@@ -206,21 +577,28 @@ impl ActorRuntime {
         // Load manifest config
         let config = ManifestConfig::from_file(&manifest_path)?;
 
-        // Initialize logging
-        let _ = FmtSubscriber::builder()
-            .with_env_filter(EnvFilter::new(config.logging.level.clone()))
-            .with_target(false)
-            .with_thread_ids(true)
-            .with_file(true)
-            .with_line_number(true)
-            .with_thread_names(true)
-            .with_writer(std::io::stdout)
-            .compact()
-            .init();
+        // Initialize logging, exporting spans when tracing is configured
+        let otlp = match &config.logging.tracing {
+            Some(tracing) => Some(telemetry::otlp_layer(tracing, &config.name)?),
+            None => None,
+        };
+        let _ = tracing_subscriber::registry()
+            .with(EnvFilter::new(config.logging.level.clone()))
+            .with(logging::fmt_layer(&config.logging)?)
+            .with(otlp)
+            .try_init();
+        CHAIN_EMITTER.set_log_events(config.logging.chain_events);
+
+        Self::from_config(config, None)
+    }
 
+    /// Start an actor from a loaded manifest. When `parent` is set the actor is
+    /// recorded as its child and the parent is notified when it exits.
+    pub fn from_config(config: ManifestConfig, parent: Option<&Supervisor>) -> Result<Self> {
         // Create store with HTTP handlers
-        let (tx, rx) = mpsc::channel(32);
-        let store = {
+        let (tx, rx) = mailbox::channel(&config.name, &config.mailbox)?;
+        let supervisor = Supervisor::new(&config.name, tx.clone());
+        let mut store = {
             let mut http_port = None;
             let mut http_server_port = None;
 
@@ -239,38 +617,88 @@ impl ActorRuntime {
                 _ => Store::new(),
             }
         };
+        store.supervisor = Some(supervisor.clone());
+
+        // Actors serving mutual TLS present the same certificate when sending
+        let http_tls = config.handlers.iter().find_map(|handler| match handler {
+            HandlerConfig::Http(config) => config.tls.as_ref(),
+            _ => None,
+        });
+        if let Some(tls) = http_tls {
+            store.http = store.http.take().map(|http| http.with_tls(tls)).transpose()?;
+        }
+
+        // Claim every handler's address before anything is started, so an
+        // unavailable address fails actor creation instead of a handler task
+        let mut handlers = Vec::new();
+        for handler_config in &config.handlers {
+            let handler: Arc<dyn HostHandler> = match handler_config {
+                HandlerConfig::Http(http_config) => {
+                    Arc::new(http::HttpHandler::from_config(http_config.clone()))
+                }
+                HandlerConfig::HttpServer(http_config) => {
+                    Arc::new(http_server::HttpServerHandler::from_config(http_config.clone()))
+                }
+            };
+            handler.bind()?;
+            handlers.push(handler);
+        }
+
+        // Make this actor's known peers addressable by name
+        for (name, address) in &config.registry {
+            ACTOR_REGISTRY.register_remote(name, address);
+        }
 
         // Create the WASM actor with the store
-        let actor = Box::new(wasm::WasmActor::new(&config, store)?);
+        let actor = Box::new(wasm::WasmActor::new(&config, store.clone())?);
 
         // Create and spawn actor process
-        let mut actor_process = ActorProcess::new(&config.name, actor, rx)?;
+        let (control_tx, control_rx) = mpsc::channel(8);
+        let mut actor_process = ActorProcess::new(&config.name, actor, rx)?
+            .with_supervisor(supervisor.clone())
+            .with_control(control_rx);
+
+        // Only an actor that started is addressable, so a failed start
+        // leaves no entry pointing at a dead mailbox
+        ACTOR_REGISTRY.register_local(&config.name, tx.clone());
+        ACTOR_REGISTRY.register_control(&config.name, control_tx.clone());
+        if let Some(parent) = parent {
+            actor_process.chain.add_event(ChainEvent::ParentLink {
+                parent_actor: parent.actor_id().to_string(),
+                timestamp: Utc::now(),
+            });
+        }
+
+        let parent = parent.cloned();
+        let actor_name = config.name.clone();
+        // The parent hears of the exit from here only, however the child stopped
         let process_handle = tokio::spawn(async move {
-            if let Err(e) = actor_process.run().await {
-                error!("Actor process failed: {}", e);
+            let reason = match actor_process.run().await {
+                Ok(_) if actor_process.stopped => "stopped".to_string(),
+                Ok(_) => "completed".to_string(),
+                Err(e) => {
+                    error!("Actor process failed: {}", e);
+                    e.to_string()
+                }
+            };
+            METRICS.remove_actor(&actor_name);
+
+            if let Some(parent) = parent {
+                parent.notify_child_exit(&actor_name, &reason).await;
             }
         });
 
         let mut handler_tasks = Vec::new();
-        for handler_config in &config.handlers {
+        for handler in &handlers {
             let tx = tx.clone();
-            let handler_config = handler_config.clone();
+            let task_handler = handler.clone();
             let task = tokio::spawn(async move {
-                let handler: Box<dyn HostHandler> = match handler_config {
-                    HandlerConfig::Http(http_config) => {
-                        Box::new(http::HttpHandler::new(http_config.port))
-                    }
-                    HandlerConfig::HttpServer(http_config) => {
-                        Box::new(http_server::HttpServerHandler::new(http_config.port))
-                    }
-                };
+                let handler_name = task_handler.name().to_string();
 
-                let handler_name = handler.name().to_string();
-
-                let start_future = handler.start(tx.clone());
+                let start_future = task_handler.start(tx.clone());
                 match start_future.await {
                     Ok(_) => {
-                        info!("Handler {} started successfully", handler_name);
+                        info!("Handler {} stopped", handler_name);
                     }
                     Err(e) => {
                         error!("Failed to start handler: {}", e);
@@ -283,20 +711,120 @@ impl ActorRuntime {
 
         Ok(Self {
             config,
+            mailbox: tx,
+            control_tx,
+            store,
+            supervisor,
             process_handle: Some(process_handle),
+            handlers,
             handler_tasks,
         })
     }
 
+    /// Number of messages waiting in this actor's mailbox
+    pub fn mailbox_depth(&self) -> usize {
+        self.mailbox.depth()
+    }
+
+    /// Send a control message and wait for its answer, for at most `timeout`.
+    /// Control messages wait for the actor to finish the message it is
+    /// handling, which may take arbitrarily long.
+    async fn control_within<T>(
+        &self,
+        timeout: Duration,
+        make: impl FnOnce(oneshot::Sender<T>) -> ControlMessage,
+    ) -> Result<T> {
+        tokio::time::timeout(timeout, self.control(make))
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "Actor {} did not respond within {:?}",
+                    self.config.name,
+                    timeout
+                )
+            })?
+    }
+
+    async fn control<T>(
+        &self,
+        make: impl FnOnce(oneshot::Sender<T>) -> ControlMessage,
+    ) -> Result<T> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.control_tx
+            .send(make(response_tx))
+            .await
+            .map_err(|_| anyhow::anyhow!("Actor {} is not running", self.config.name))?;
+        response_rx
+            .await
+            .map_err(|_| anyhow::anyhow!("Actor {} did not respond", self.config.name))
+    }
+
+    pub async fn health_check(&self) -> Result<ActorHealth> {
+        self.control_within(CONTROL_TIMEOUT, |response_channel| {
+            ControlMessage::HealthCheck { response_channel }
+        })
+        .await
+    }
+
+    pub async fn snapshot(&self) -> Result<ActorSnapshot> {
+        self.control_within(CONTROL_TIMEOUT, |response_channel| {
+            ControlMessage::Snapshot { response_channel }
+        })
+        .await
+    }
+
+    /// Replace the running component, keeping the actor's chain and state
+    pub async fn upgrade(&self, component_path: PathBuf) -> Result<()> {
+        let mut config = self.config.clone();
+        config.component_path = component_path;
+        let actor = Box::new(wasm::WasmActor::new(&config, self.store.clone())?);
+        let component = config.component_path.display().to_string();
+
+        self.control(|response_channel| ControlMessage::Upgrade {
+            actor,
+            component,
+            response_channel,
+        })
+        .await?
+    }
+
+    /// Stop accepting input, process what is already queued, and stop the actor
     pub async fn shutdown(&mut self) -> Result<()> {
-        // Stop all handlers
-        for task in self.handler_tasks.drain(..) {
-            task.abort();
+        let drain_timeout = Duration::from_millis(self.config.mailbox.drain_timeout_ms);
+
+        // Stop accepting messages from other actors
+        ACTOR_REGISTRY.unregister(&self.config.name);
+
+        // Stop handlers so no new requests reach the mailbox
+        for handler in &self.handlers {
+            if let Err(e) = handler.stop().await {
+                error!("Failed to stop handler {}: {}", handler.name(), e);
+            }
+        }
+        for mut task in self.handler_tasks.drain(..) {
+            if tokio::time::timeout(drain_timeout, &mut task).await.is_err() {
+                task.abort();
+            }
+        }
+
+        // Stop any children this actor spawned
+        self.supervisor.stop_all().await;
+
+        // Drain the mailbox and let the actor process exit
+        if let Err(e) = self
+            .control_within(drain_timeout, |response_channel| ControlMessage::Shutdown {
+                drain_timeout,
+                response_channel,
+            })
+            .await
+        {
+            warn!("Actor {} could not drain its mailbox: {}", self.config.name, e);
         }
 
-        // Cancel actor process
-        if let Some(handle) = self.process_handle.take() {
-            handle.abort();
+        if let Some(mut handle) = self.process_handle.take() {
+            if tokio::time::timeout(drain_timeout, &mut handle).await.is_err() {
+                handle.abort();
+            }
         }
 
         Ok(())
@@ -0,0 +1,385 @@
+use anyhow::{anyhow, Context, Result};
+use futures::future::BoxFuture;
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, StatusCode, Uri};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::task::{self, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+
+use crate::config::{BindAddress, TlsConfig};
+use crate::tls;
+
+pub type Request = hyper::Request<Body>;
+pub type Response = hyper::Response<Body>;
+
+/// Answers requests for a handler or the event server
+pub type Service = Arc<dyn Fn(Request) -> BoxFuture<'static, Response> + Send + Sync>;
+
+/// Wraps an async function as a [`Service`]
+pub fn service<F, Fut>(handler: F) -> Service
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send + 'static,
+{
+    Arc::new(move |req| Box::pin(handler(req)))
+}
+
+/// Where a request arrived, added to the extensions of every request
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionInfo {
+    pub peer: Option<SocketAddr>,
+    pub local: Option<SocketAddr>,
+    pub tls: bool,
+}
+
+/// The request URI before a mount's path prefix was removed
+#[derive(Debug, Clone)]
+pub struct OriginalUri(pub Uri);
+
+/// A bound TCP or unix domain socket listener
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+impl Listener {
+    /// Binds immediately, so a taken or invalid address is reported to the
+    /// caller rather than to whichever task later serves the listener
+    pub fn bind(address: &BindAddress) -> Result<Self> {
+        let bind = || -> io::Result<Self> {
+            match address {
+                BindAddress::Tcp(addr) => {
+                    let listener = std::net::TcpListener::bind(addr)?;
+                    listener.set_nonblocking(true)?;
+                    TcpListener::from_std(listener).map(Self::Tcp)
+                }
+                BindAddress::Unix(path) => {
+                    remove_stale_socket(path)?;
+                    let listener = std::os::unix::net::UnixListener::bind(path)?;
+                    listener.set_nonblocking(true)?;
+                    UnixListener::from_std(listener).map(Self::Unix)
+                }
+            }
+        };
+        bind().with_context(|| format!("Failed to bind {}", address))
+    }
+
+    /// Waits for a connection, with the peer's address for TCP clients
+    pub async fn accept(&self) -> io::Result<(Connection, Option<SocketAddr>)> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                Ok((Connection::Tcp(stream), Some(peer)))
+            }
+            Self::Unix(listener) => {
+                let (stream, _) = listener.accept().await?;
+                Ok((Connection::Unix(stream), None))
+            }
+        }
+    }
+}
+
+/// Removes a socket file left behind by a server that is gone, so its path can
+/// be bound again. Anything else at the path is left for bind to refuse.
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let is_socket = std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket());
+    // A socket that still accepts connections belongs to a live server
+    let refused = || {
+        matches!(
+            std::os::unix::net::UnixStream::connect(path),
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused
+        )
+    };
+    if is_socket && refused() {
+        debug!("Removing stale socket {}", path.display());
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// A connection accepted by a [`Listener`]
+pub enum Connection {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Connection {
+    fn local_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Tcp(stream) => stream.local_addr().ok(),
+            Self::Unix(_) => None,
+        }
+    }
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            Self::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// How long to wait before accepting again after a failed accept
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Serves `service` on `listener`, over TLS when an acceptor is given, until
+/// the returned future is dropped
+pub async fn serve(listener: Listener, tls: Option<TlsAcceptor>, service: Service) {
+    loop {
+        // Failures such as running out of file descriptors are usually brief,
+        // so they must not take the listener down
+        let (connection, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Failed to accept connection: {}", e);
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let info = ConnectionInfo {
+            peer,
+            local: connection.local_addr(),
+            tls: tls.is_some(),
+        };
+        let service = service.clone();
+        let tls = tls.clone();
+
+        tokio::spawn(async move {
+            let result = match tls {
+                Some(acceptor) => match acceptor.accept(connection).await {
+                    Ok(stream) => serve_connection(stream, info, service).await,
+                    // A failed handshake, such as a missing client certificate, only closes this connection
+                    Err(e) => {
+                        warn!("TLS handshake with {:?} failed: {}", peer, e);
+                        return;
+                    }
+                },
+                None => serve_connection(connection, info, service).await,
+            };
+            if let Err(e) = result {
+                debug!("Connection from {:?} closed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn serve_connection<S>(stream: S, info: ConnectionInfo, service: Service) -> hyper::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let service = service_fn(move |mut req: Request| {
+        req.extensions_mut().insert(info);
+        let response = service(req);
+        async move { Ok::<_, Infallible>(response.await) }
+    });
+    Http::new()
+        .serve_connection(stream, service)
+        .with_upgrades()
+        .await
+}
+
+// Path prefixes served on one address, each with the service once it is started
+type Mounts = RwLock<Vec<(String, Option<Service>)>>;
+
+struct SharedListener {
+    mounts: Arc<Mounts>,
+    /// TLS settings the address was bound with, which every mount shares
+    tls: Option<TlsConfig>,
+    /// Socket file created by binding a unix domain socket
+    socket: Option<PathBuf>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for SharedListener {
+    fn drop(&mut self) {
+        // The last mount is gone, so stop accepting connections
+        self.task.abort();
+        // Removed now rather than when the task ends, so the path can be
+        // bound again straight away
+        if let Some(path) = &self.socket {
+            if let Err(e) = std::fs::remove_file(path) {
+                warn!("Failed to remove socket {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+lazy_static! {
+    static ref LISTENERS: Mutex<HashMap<BindAddress, Weak<SharedListener>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// A path prefix claimed on an address that several handlers can share
+pub struct Mount {
+    listener: Arc<SharedListener>,
+    prefix: String,
+}
+
+impl Mount {
+    /// Claims `prefix` on `address`, binding the address if nothing in this
+    /// process listens there yet. Every mount sharing an address must ask
+    /// for the same TLS settings.
+    pub fn bind(address: &BindAddress, tls: Option<&TlsConfig>, prefix: &str) -> Result<Self> {
+        let prefix = prefix.trim_end_matches('/').to_string();
+        let mut listeners = LISTENERS.lock().unwrap();
+
+        let listener = match listeners.get(address).and_then(Weak::upgrade) {
+            Some(listener) => {
+                if listener.tls.as_ref() != tls {
+                    return Err(anyhow!(
+                        "{} is already served with different TLS settings",
+                        address
+                    ));
+                }
+                listener
+            }
+            None => {
+                let acceptor = tls.map(tls::acceptor).transpose()?;
+                let bound = Listener::bind(address)?;
+                let mounts = Arc::new(Mounts::default());
+                let service = dispatch(mounts.clone());
+                let task = tokio::spawn(serve(bound, acceptor, service));
+                info!("Listening on {}", address);
+
+                let socket = match address {
+                    BindAddress::Unix(path) => Some(path.clone()),
+                    BindAddress::Tcp(_) => None,
+                };
+                let listener = Arc::new(SharedListener {
+                    mounts,
+                    tls: tls.cloned(),
+                    socket,
+                    task,
+                });
+                listeners.insert(address.clone(), Arc::downgrade(&listener));
+                listener
+            }
+        };
+
+        {
+            let mut mounts = listener.mounts.write().unwrap();
+            if mounts.iter().any(|(mounted, _)| *mounted == prefix) {
+                return Err(anyhow!(
+                    "Path prefix '{}/' is already served on {}",
+                    prefix,
+                    address
+                ));
+            }
+            mounts.push((prefix.clone(), None));
+        }
+
+        Ok(Self { listener, prefix })
+    }
+
+    /// Routes requests under the prefix to `service`; until then they get 503
+    pub fn serve(&self, service: Service) {
+        let mut mounts = self.listener.mounts.write().unwrap();
+        if let Some((_, mounted)) = mounts.iter_mut().find(|(p, _)| *p == self.prefix) {
+            *mounted = Some(service);
+        }
+    }
+}
+
+impl Drop for Mount {
+    fn drop(&mut self) {
+        let mut mounts = self.listener.mounts.write().unwrap();
+        mounts.retain(|(prefix, _)| *prefix != self.prefix);
+    }
+}
+
+/// Whether `path` is `prefix` itself or lies below it
+pub(crate) fn is_under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+fn status_response(status: StatusCode) -> Response {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+/// Sends each request to the mount with the longest matching prefix, with the
+/// prefix removed from its path
+fn dispatch(mounts: Arc<Mounts>) -> Service {
+    Arc::new(move |mut req| {
+        let original = req.uri().clone();
+        let matched = mounts
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(prefix, _)| is_under(original.path(), prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(prefix, service)| (prefix.clone(), service.clone()));
+
+        let (prefix, service) = match matched {
+            Some((prefix, Some(service))) => (prefix, service),
+            Some((_, None)) => {
+                return Box::pin(async { status_response(StatusCode::SERVICE_UNAVAILABLE) })
+            }
+            None => return Box::pin(async { status_response(StatusCode::NOT_FOUND) }),
+        };
+
+        if !prefix.is_empty() {
+            let path = &original.path()[prefix.len()..];
+            let path = if path.is_empty() { "/" } else { path };
+            let stripped = match original.query() {
+                Some(query) => format!("{}?{}", path, query),
+                None => path.to_string(),
+            };
+            if let Ok(uri) = stripped.parse() {
+                *req.uri_mut() = uri;
+            }
+        }
+        req.extensions_mut().insert(OriginalUri(original));
+        service(req)
+    })
+}
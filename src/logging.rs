@@ -1,6 +1,23 @@
+use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::config::{LogFormat, LogOutput, LoggingConfig};
+
+/// Target chain commits are logged under when `chain_events` is enabled
+pub const CHAIN_TARGET: &str = "theater::chain";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChainEvent {
@@ -12,11 +29,22 @@ pub struct ChainEvent {
     pub parent: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ChainEventType {
+    /// The first entry in an actor's chain
     Init,
+    /// A new state committed after handling input
     StateTransition,
+    /// A message from another actor
     Message,
+    /// A request or message from outside the runtime
+    Input,
+    /// What the actor returned for its input
+    Output,
+    /// Children spawned or stopped, parent links, upgrades and shutdown
+    Lifecycle,
+    /// Failed handling and responses nobody was waiting for
+    Error,
 }
 
 impl fmt::Display for ChainEvent {
@@ -37,3 +65,189 @@ impl fmt::Display for ChainEvent {
         writeln!(f, "----------------------------------")
     }
 }
+
+/// Layer writing log events to the output and in the format in `config`
+pub fn fmt_layer<S>(config: &LoggingConfig) -> Result<Box<dyn Layer<S> + Send + Sync>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_target(false)
+        .with_thread_ids(true)
+        .with_file(true)
+        .with_line_number(true)
+        .with_thread_names(true);
+
+    let layer = match &config.output {
+        LogOutput::Stdout => match config.format {
+            LogFormat::Text => layer.with_writer(io::stdout).compact().boxed(),
+            LogFormat::Json => layer
+                .with_writer(io::stdout)
+                .event_format(JsonLines)
+                .boxed(),
+        },
+        LogOutput::File => {
+            let path = config
+                .file_path
+                .as_ref()
+                .ok_or_else(|| anyhow!("Logging to a file requires file_path"))?;
+            let file = Arc::new(RollingFile::open(
+                path,
+                config.max_file_bytes,
+                config.max_files,
+            )?);
+            LOG_FILES.lock().unwrap().push(Arc::downgrade(&file));
+            let layer = layer.with_writer(file).with_ansi(false);
+            match config.format {
+                LogFormat::Text => layer.compact().boxed(),
+                LogFormat::Json => layer.event_format(JsonLines).boxed(),
+            }
+        }
+    };
+    Ok(layer)
+}
+
+/// Write out everything logged so far, to stdout and to every open log file
+pub fn flush() {
+    let _ = io::stdout().flush();
+    LOG_FILES
+        .lock()
+        .unwrap()
+        .retain(|file| match file.upgrade() {
+            Some(file) => {
+                let _ = file.sync();
+                true
+            }
+            None => false,
+        });
+}
+
+/// Formats each event as a JSON object on its own line, with the names of
+/// the spans it was recorded in
+pub struct JsonLines;
+
+impl<S, N> FormatEvent<S, N> for JsonLines
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut line = Map::new();
+        line.insert("timestamp".into(), Utc::now().to_rfc3339().into());
+        line.insert("level".into(), metadata.level().as_str().into());
+        line.insert("target".into(), metadata.target().into());
+        event.record(&mut JsonFields(&mut line));
+        if let Some(scope) = ctx.event_scope() {
+            let spans: Vec<Value> = scope.from_root().map(|span| span.name().into()).collect();
+            line.insert("spans".into(), spans.into());
+        }
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
+
+/// Collects an event's fields into a JSON object
+struct JsonFields<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonFields<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{:?}", value).into());
+    }
+}
+
+/// Log file that is renamed to `<path>.1` once it reaches `max_bytes`,
+/// shifting older files up and removing any past `<path>.<max_files>`
+pub struct RollingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    current: Mutex<(File, u64)>,
+}
+
+impl RollingFile {
+    pub fn open(path: &Path, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            max_files,
+            current: Mutex::new((file, size)),
+        })
+    }
+
+    /// Flush the current file and wait until its contents reach the disk
+    pub fn sync(&self) -> io::Result<()> {
+        let mut current = self.current.lock().unwrap();
+        current.0.flush()?;
+        current.0.sync_data()
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+
+    fn rotate(&self) -> io::Result<File> {
+        if self.max_files == 0 {
+            return File::create(&self.path);
+        }
+        for n in (1..self.max_files).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))?;
+        File::create(&self.path)
+    }
+}
+
+impl Write for &RollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut current = self.current.lock().unwrap();
+        // Rotate between lines, never splitting one across files
+        if self.max_bytes > 0 && current.1 > 0 && current.1 + buf.len() as u64 > self.max_bytes {
+            *current = (self.rotate()?, 0);
+        }
+        let written = current.0.write(buf)?;
+        current.1 += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.current.lock().unwrap().0.flush()
+    }
+}
+
+// Log files opened by `fmt_layer`, for `flush`
+lazy_static::lazy_static! {
+    static ref LOG_FILES: Mutex<Vec<Weak<RollingFile>>> = Mutex::new(Vec::new());
+}
@@ -0,0 +1,229 @@
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::{mpsc, Mutex};
+use tracing::{debug, warn};
+
+use crate::config::{MailboxConfig, OverflowPolicy};
+use crate::metrics::METRICS;
+use crate::{ActorMessage, ActorOutput, MessageMetadata};
+
+/// Status returned to HTTP callers whose request was still queued at shutdown
+const SHUTDOWN_STATUS: u16 = 503;
+
+#[derive(Error, Debug)]
+pub enum MailboxError {
+    #[error("Mailbox is full")]
+    Full,
+
+    #[error("Mailbox is closed")]
+    Closed,
+}
+
+/// Create a bounded mailbox for `actor` that applies the configured overflow
+/// policy and reports its depth as a metric
+pub fn channel(actor: &str, config: &MailboxConfig) -> anyhow::Result<(Mailbox, MailboxReceiver)> {
+    if config.capacity == 0 {
+        anyhow::bail!("Mailbox capacity must be at least 1");
+    }
+
+    let actor: Arc<str> = Arc::from(actor);
+    let (tx, rx) = mpsc::channel(config.capacity);
+    let receiver = MailboxReceiver {
+        rx: Arc::new(Mutex::new(rx)),
+        actor: Some(actor.clone()),
+    };
+    let mailbox = Mailbox {
+        tx,
+        policy: config.overflow.clone(),
+        reject_status: config.reject_status,
+        receiver: Some(receiver.rx.clone()),
+        actor: Some(actor),
+    };
+    Ok((mailbox, receiver))
+}
+
+/// Sending side of an actor's mailbox
+#[derive(Clone, Debug)]
+pub struct Mailbox {
+    tx: mpsc::Sender<ActorMessage>,
+    policy: OverflowPolicy,
+    reject_status: u16,
+    // Shared with the receiver so the oldest message can be evicted
+    receiver: Option<Arc<Mutex<mpsc::Receiver<ActorMessage>>>>,
+    /// Actor the depth metric is labelled with, if it is reported
+    actor: Option<Arc<str>>,
+}
+
+impl Mailbox {
+    /// Deliver a message, applying the overflow policy if the mailbox is full
+    pub async fn send(&self, msg: ActorMessage) -> Result<(), MailboxError> {
+        let result = self.deliver(msg).await;
+        if result.is_ok() {
+            self.record_depth();
+        }
+        result
+    }
+
+    async fn deliver(&self, msg: ActorMessage) -> Result<(), MailboxError> {
+        let msg = match self.tx.try_send(msg) {
+            Ok(()) => {
+                debug!(mailbox_depth = self.depth(), "[MAILBOX] Message queued");
+                return Ok(());
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => return Err(MailboxError::Closed),
+            Err(mpsc::error::TrySendError::Full(msg)) => msg,
+        };
+
+        match self.policy {
+            OverflowPolicy::Block => {
+                warn!(
+                    mailbox_depth = self.depth(),
+                    "[MAILBOX] Mailbox full, waiting for capacity"
+                );
+                self.tx.send(msg).await.map_err(|_| MailboxError::Closed)
+            }
+            OverflowPolicy::Reject => {
+                warn!(
+                    mailbox_depth = self.depth(),
+                    "[MAILBOX] Mailbox full, rejecting message"
+                );
+                Err(MailboxError::Full)
+            }
+            OverflowPolicy::DropOldest => self.send_dropping_oldest(msg).await,
+        }
+    }
+
+    /// Deliver a message without waiting, failing if the mailbox is full
+    pub fn try_send(&self, msg: ActorMessage) -> Result<(), MailboxError> {
+        self.tx.try_send(msg).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => MailboxError::Full,
+            mpsc::error::TrySendError::Closed(_) => MailboxError::Closed,
+        })?;
+        self.record_depth();
+        Ok(())
+    }
+
+    async fn send_dropping_oldest(&self, mut msg: ActorMessage) -> Result<(), MailboxError> {
+        let Some(receiver) = &self.receiver else {
+            return self.tx.send(msg).await.map_err(|_| MailboxError::Closed);
+        };
+
+        loop {
+            // Wait for either free capacity or the receiver, rather than
+            // retrying. The actor only holds the receiver while waiting on an
+            // empty mailbox, in which case capacity frees up instead.
+            tokio::select! {
+                permit = self.tx.reserve() => {
+                    let permit = permit.map_err(|_| MailboxError::Closed)?;
+                    permit.send(msg);
+                    return Ok(());
+                }
+                mut rx = receiver.lock() => {
+                    if let Ok(oldest) = rx.try_recv() {
+                        warn!(
+                            mailbox_depth = self.depth(),
+                            "[MAILBOX] Mailbox full, dropping oldest message"
+                        );
+                        reject(oldest, self.reject_status);
+                    }
+                }
+            }
+
+            // Other senders may have taken the freed slot, in which case evict again
+            match self.tx.try_send(msg) {
+                Ok(()) => return Ok(()),
+                Err(mpsc::error::TrySendError::Closed(_)) => return Err(MailboxError::Closed),
+                Err(mpsc::error::TrySendError::Full(returned)) => msg = returned,
+            }
+        }
+    }
+
+    /// Number of messages currently waiting in the mailbox
+    pub fn depth(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
+    }
+
+    /// HTTP status handlers should answer with when a message is rejected
+    pub fn reject_status(&self) -> u16 {
+        self.reject_status
+    }
+
+    fn record_depth(&self) {
+        record_depth(self.actor.as_deref(), self.depth());
+    }
+}
+
+impl From<mpsc::Sender<ActorMessage>> for Mailbox {
+    fn from(tx: mpsc::Sender<ActorMessage>) -> Self {
+        let config = MailboxConfig::default();
+        Self {
+            tx,
+            policy: config.overflow,
+            reject_status: config.reject_status,
+            receiver: None,
+            actor: None,
+        }
+    }
+}
+
+/// Receiving side of an actor's mailbox, owned by the `ActorProcess`
+pub struct MailboxReceiver {
+    rx: Arc<Mutex<mpsc::Receiver<ActorMessage>>>,
+    actor: Option<Arc<str>>,
+}
+
+impl MailboxReceiver {
+    pub async fn recv(&mut self) -> Option<ActorMessage> {
+        let mut rx = self.rx.lock().await;
+        let msg = rx.recv().await;
+        record_depth(self.actor.as_deref(), rx.len());
+        msg
+    }
+
+    /// Stop accepting new messages while keeping queued ones available
+    pub async fn close(&mut self) {
+        self.rx.lock().await.close();
+    }
+
+    /// Turn away every queued message, returning how many there were
+    pub async fn reject_remaining(&mut self) -> usize {
+        let mut rx = self.rx.lock().await;
+        let mut rejected = 0;
+        while let Ok(msg) = rx.try_recv() {
+            reject(msg, SHUTDOWN_STATUS);
+            rejected += 1;
+        }
+        record_depth(self.actor.as_deref(), rx.len());
+        rejected
+    }
+}
+
+impl From<mpsc::Receiver<ActorMessage>> for MailboxReceiver {
+    fn from(rx: mpsc::Receiver<ActorMessage>) -> Self {
+        Self {
+            rx: Arc::new(Mutex::new(rx)),
+            actor: None,
+        }
+    }
+}
+
+fn record_depth(actor: Option<&str>, depth: usize) {
+    if let Some(actor) = actor {
+        METRICS.mailbox_depth.set(&[("actor", actor)], depth as f64);
+    }
+}
+
+/// Answer a message that will never reach the actor so HTTP callers are not left hanging
+fn reject(msg: ActorMessage, status: u16) {
+    if let Some(MessageMetadata::HttpRequest {
+        response_channel,
+        correlation_id,
+    }) = msg.metadata
+    {
+        let _ = response_channel.send(ActorOutput::http_error(
+            status,
+            "Actor is not accepting requests",
+            &correlation_id,
+        ));
+    }
+}
@@ -2,6 +2,8 @@ use anyhow::Result;
 use chrono::Utc;
 use clap::Parser;
 use std::path::PathBuf;
+use theater::config::{BindAddress, DEFAULT_BIND_ADDRESS};
+use theater::listener::Mount;
 use theater::ActorRuntime;
 use tracing::info;
 
@@ -15,6 +17,10 @@ struct Args {
     /// Port for the event server
     #[arg(short, long, default_value = "3030")]
     event_port: u16,
+
+    /// Address for the event server: a host, an IP address or `unix:<path>`
+    #[arg(long, default_value = DEFAULT_BIND_ADDRESS)]
+    event_address: String,
 }
 
 #[tokio::main]
@@ -27,12 +33,12 @@ async fn main() -> Result<()> {
         return Err(anyhow::anyhow!("Manifest file not found: {}", args.manifest.display()));
     }
 
-    // Start the event server
-    let event_port = args.event_port;
-    tokio::spawn(async move {
-        theater::event_server::run_event_server(event_port).await;
-    });
-    info!("Event server starting on port {}", event_port);
+    // Start the event server, failing now if its address cannot be bound.
+    // Handlers can share its address by serving under a path prefix.
+    let event_address = BindAddress::parse(&args.event_address, args.event_port)?;
+    let events = Mount::bind(&event_address, None, "/")?;
+    events.serve(theater::event_server::service());
+    info!("Event server starting on {}", event_address);
 
     // Create and initialize the runtime
     let mut runtime = ActorRuntime::from_file(args.manifest).await?;
@@ -45,5 +51,8 @@ async fn main() -> Result<()> {
     info!("Shutting down...");
     runtime.shutdown().await?;
 
+    // Export any spans still buffered before the process exits
+    theater::telemetry::shutdown();
+
     Ok(())
 }
\ No newline at end of file
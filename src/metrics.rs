@@ -0,0 +1,345 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+use crate::listener::Service;
+
+/// Label names and values identifying one series of a metric
+type Labels = Vec<(&'static str, String)>;
+
+/// Latency buckets in seconds, from 100µs to 10s
+const SECONDS_BUCKETS: &[f64] = &[
+    0.0001, 0.0005, 0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Runtime metrics, rendered in the Prometheus text format by the event server
+pub struct Metrics {
+    pub messages_handled: Counter,
+    pub handler_duration: Histogram,
+    pub wasm_instantiation: Histogram,
+    pub fuel_consumed: Counter,
+    pub mailbox_depth: Gauge,
+    pub chain_length: Gauge,
+    pub http_responses: Counter,
+    pub contract_rejections: Counter,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            messages_handled: Counter::new(
+                "theater_messages_handled_total",
+                "Messages an actor has handled, by outcome",
+            ),
+            handler_duration: Histogram::new(
+                "theater_handler_duration_seconds",
+                "Time an actor took to handle a message",
+                SECONDS_BUCKETS,
+            ),
+            wasm_instantiation: Histogram::new(
+                "theater_wasm_instantiation_seconds",
+                "Time taken to instantiate an actor's component",
+                SECONDS_BUCKETS,
+            ),
+            fuel_consumed: Counter::new(
+                "theater_wasm_fuel_consumed_total",
+                "Fuel consumed by calls into an actor's component, by export",
+            ),
+            mailbox_depth: Gauge::new(
+                "theater_mailbox_depth",
+                "Messages waiting in an actor's mailbox",
+            ),
+            chain_length: Gauge::new("theater_chain_length", "Entries in an actor's hash chain"),
+            http_responses: Counter::new(
+                "theater_http_responses_total",
+                "Responses sent by HTTP handlers, by status code",
+            ),
+            contract_rejections: Counter::new(
+                "theater_contract_rejections_total",
+                "States rejected by an actor's state contract",
+            ),
+        }
+    }
+
+    /// Drop every series labelled with `actor`, once it has stopped
+    pub fn remove_actor(&self, actor: &str) {
+        self.messages_handled.remove("actor", actor);
+        self.handler_duration.remove("actor", actor);
+        self.wasm_instantiation.remove("actor", actor);
+        self.fuel_consumed.remove("actor", actor);
+        self.mailbox_depth.remove("actor", actor);
+        self.chain_length.remove("actor", actor);
+        self.contract_rejections.remove("actor", actor);
+    }
+
+    /// Every metric in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.messages_handled.render(&mut out);
+        self.handler_duration.render(&mut out);
+        self.wasm_instantiation.render(&mut out);
+        self.fuel_consumed.render(&mut out);
+        self.mailbox_depth.render(&mut out);
+        self.chain_length.render(&mut out);
+        self.http_responses.render(&mut out);
+        self.contract_rejections.render(&mut out);
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    values: Mutex<BTreeMap<Labels, u64>>,
+}
+
+impl Counter {
+    fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn inc(&self, labels: &[(&'static str, &str)]) {
+        self.inc_by(labels, 1);
+    }
+
+    pub fn inc_by(&self, labels: &[(&'static str, &str)], amount: u64) {
+        *self
+            .values
+            .lock()
+            .unwrap()
+            .entry(owned(labels))
+            .or_default() += amount;
+    }
+
+    /// Current value of the series with `labels`
+    pub fn get(&self, labels: &[(&'static str, &str)]) -> u64 {
+        self.values
+            .lock()
+            .unwrap()
+            .get(&owned(labels))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Drop every series where `label` is `value`
+    pub fn remove(&self, label: &str, value: &str) {
+        self.values
+            .lock()
+            .unwrap()
+            .retain(|labels, _| !has_label(labels, label, value));
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, "counter");
+        for (labels, value) in self.values.lock().unwrap().iter() {
+            series(out, self.name, labels, None, *value as f64);
+        }
+    }
+}
+
+pub struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    values: Mutex<BTreeMap<Labels, f64>>,
+}
+
+impl Gauge {
+    fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn set(&self, labels: &[(&'static str, &str)], value: f64) {
+        self.values.lock().unwrap().insert(owned(labels), value);
+    }
+
+    /// Current value of the series with `labels`
+    pub fn get(&self, labels: &[(&'static str, &str)]) -> f64 {
+        self.values
+            .lock()
+            .unwrap()
+            .get(&owned(labels))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Drop every series where `label` is `value`
+    pub fn remove(&self, label: &str, value: &str) {
+        self.values
+            .lock()
+            .unwrap()
+            .retain(|labels, _| !has_label(labels, label, value));
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, "gauge");
+        for (labels, value) in self.values.lock().unwrap().iter() {
+            series(out, self.name, labels, None, *value);
+        }
+    }
+}
+
+pub struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    buckets: &'static [f64],
+    values: Mutex<BTreeMap<Labels, Observations>>,
+}
+
+#[derive(Default)]
+struct Observations {
+    /// Observations at or below each bucket's upper bound
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(name: &'static str, help: &'static str, buckets: &'static [f64]) -> Self {
+        Self {
+            name,
+            help,
+            buckets,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn observe(&self, labels: &[(&'static str, &str)], value: f64) {
+        let mut values = self.values.lock().unwrap();
+        let observations = values.entry(owned(labels)).or_default();
+        observations.counts.resize(self.buckets.len(), 0);
+        for (count, bound) in observations.counts.iter_mut().zip(self.buckets) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        observations.sum += value;
+        observations.count += 1;
+    }
+
+    /// Number of observations in the series with `labels`
+    pub fn count(&self, labels: &[(&'static str, &str)]) -> u64 {
+        self.values
+            .lock()
+            .unwrap()
+            .get(&owned(labels))
+            .map_or(0, |observations| observations.count)
+    }
+
+    /// Drop every series where `label` is `value`
+    pub fn remove(&self, label: &str, value: &str) {
+        self.values
+            .lock()
+            .unwrap()
+            .retain(|labels, _| !has_label(labels, label, value));
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, "histogram");
+        let bucket = format!("{}_bucket", self.name);
+        for (labels, observations) in self.values.lock().unwrap().iter() {
+            for (count, bound) in observations.counts.iter().zip(self.buckets) {
+                let le = bound.to_string();
+                series(out, &bucket, labels, Some(&le), *count as f64);
+            }
+            series(
+                out,
+                &bucket,
+                labels,
+                Some("+Inf"),
+                observations.count as f64,
+            );
+            series(
+                out,
+                &format!("{}_sum", self.name),
+                labels,
+                None,
+                observations.sum,
+            );
+            series(
+                out,
+                &format!("{}_count", self.name),
+                labels,
+                None,
+                observations.count as f64,
+            );
+        }
+    }
+}
+
+/// Counts the responses `service` sends by status code, labelled with the
+/// handler's name and the address it serves
+pub fn count_responses(service: Service, handler: &'static str, address: String) -> Service {
+    Arc::new(move |req| {
+        let response = service(req);
+        let address = address.clone();
+        Box::pin(async move {
+            let response = response.await;
+            METRICS.http_responses.inc(&[
+                ("handler", handler),
+                ("address", &address),
+                ("status", response.status().as_str()),
+            ]);
+            response
+        })
+    })
+}
+
+fn owned(labels: &[(&'static str, &str)]) -> Labels {
+    labels
+        .iter()
+        .map(|(name, value)| (*name, value.to_string()))
+        .collect()
+}
+
+fn has_label(labels: &Labels, label: &str, value: &str) -> bool {
+    labels
+        .iter()
+        .any(|(name, current)| *name == label && current == value)
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// One sample line, with `le` added for histogram buckets
+fn series(out: &mut String, name: &str, labels: &Labels, le: Option<&str>, value: f64) {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(label, value)| format!("{}=\"{}\"", label, escape(value)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+    if pairs.is_empty() {
+        let _ = writeln!(out, "{} {}", name, value);
+    } else {
+        let _ = writeln!(out, "{}{{{}}} {}", name, pairs.join(","), value);
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Global instance
+lazy_static::lazy_static! {
+    pub static ref METRICS: Metrics = Metrics::new();
+}
@@ -0,0 +1,355 @@
+use async_compression::tokio::bufread::{BrotliEncoder, GzipEncoder};
+use async_compression::Level;
+use futures::{StreamExt, TryStreamExt};
+use hyper::body::HttpBody;
+use hyper::header::{self, HeaderName, HeaderValue};
+use hyper::{Body, Method, StatusCode};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio_util::io::{ReaderStream, StreamReader};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::MiddlewareConfig;
+use crate::listener::{ConnectionInfo, Request, Response, Service};
+
+/// Wrap a handler's service in a middleware stage declared in an
+/// `Http-server` handler config
+pub fn apply(inner: Service, config: &MiddlewareConfig) -> anyhow::Result<Service> {
+    let service: Service = match config {
+        MiddlewareConfig::Cors {
+            allow_origins,
+            allow_methods,
+            allow_headers,
+            max_age_secs,
+        } => {
+            let cors = Arc::new(Cors {
+                allow_origins: allow_origins.clone(),
+                allow_methods: header_value(allow_methods)?,
+                allow_headers: header_value(allow_headers)?,
+                max_age: header_value(&max_age_secs.to_string())?,
+            });
+            Arc::new(move |req| {
+                let cors = cors.clone();
+                let inner = inner.clone();
+                Box::pin(async move { cors.handle(req, inner).await })
+            })
+        }
+        MiddlewareConfig::Logging => Arc::new(move |req| {
+            let inner = inner.clone();
+            Box::pin(log_request(req, inner))
+        }),
+        MiddlewareConfig::BodyLimit { max_bytes } => {
+            let max_bytes = *max_bytes;
+            Arc::new(move |req| {
+                let inner = inner.clone();
+                Box::pin(limit_body(req, inner, max_bytes))
+            })
+        }
+        MiddlewareConfig::Compression { min_bytes } => {
+            let min_bytes = *min_bytes;
+            Arc::new(move |req| {
+                let inner = inner.clone();
+                Box::pin(compress(req, inner, min_bytes))
+            })
+        }
+        MiddlewareConfig::RequestId { header } => {
+            let header = HeaderName::from_bytes(header.as_bytes()).map_err(|_| {
+                anyhow::anyhow!("Invalid header name in middleware config: {}", header)
+            })?;
+            Arc::new(move |req| {
+                let inner = inner.clone();
+                Box::pin(request_id(req, inner, header.clone()))
+            })
+        }
+    };
+    Ok(service)
+}
+
+fn header_value(value: &str) -> anyhow::Result<HeaderValue> {
+    value
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid header value in middleware config: {}", value))
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    let body = serde_json::json!({ "error": message }).to_string();
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+/// Answers preflight requests and adds CORS headers; no origins means any origin
+struct Cors {
+    allow_origins: Vec<String>,
+    allow_methods: HeaderValue,
+    allow_headers: HeaderValue,
+    max_age: HeaderValue,
+}
+
+impl Cors {
+    async fn handle(&self, req: Request, inner: Service) -> Response {
+        let Some(origin) = req.headers().get(header::ORIGIN).cloned() else {
+            return inner(req).await;
+        };
+        let allowed = self.allow_origins.is_empty()
+            || self
+                .allow_origins
+                .iter()
+                .any(|allowed| allowed.as_bytes() == origin.as_bytes());
+
+        let preflight = req.method() == Method::OPTIONS
+            && req
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+        if preflight {
+            if !allowed {
+                return error_response(StatusCode::FORBIDDEN, "Origin not allowed");
+            }
+            let mut response = Response::new(Body::empty());
+            let headers = response.headers_mut();
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_METHODS,
+                self.allow_methods.clone(),
+            );
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_HEADERS,
+                self.allow_headers.clone(),
+            );
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, self.max_age.clone());
+            headers.append(header::VARY, HeaderValue::from_static("Origin"));
+            return response;
+        }
+
+        let mut response = inner(req).await;
+        if allowed {
+            let headers = response.headers_mut();
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+            headers.append(header::VARY, HeaderValue::from_static("Origin"));
+        }
+        response
+    }
+}
+
+/// Logs each request with its status and how long the actor took
+async fn log_request(req: Request, inner: Service) -> Response {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let peer = req
+        .extensions()
+        .get::<ConnectionInfo>()
+        .and_then(|info| info.peer)
+        .map(|peer| peer.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    let start = Instant::now();
+
+    let response = inner(req).await;
+    info!(
+        "[HTTP] {} {} from {} -> {} in {:?}",
+        method,
+        path,
+        peer,
+        response.status().as_u16(),
+        start.elapsed()
+    );
+    response
+}
+
+/// Rejects request bodies larger than `max_bytes` with 413
+async fn limit_body(req: Request, inner: Service, max_bytes: usize) -> Response {
+    let too_large = || {
+        warn!("[HTTP] Rejecting body over {} bytes", max_bytes);
+        error_response(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large")
+    };
+
+    let declared = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok()?.parse::<usize>().ok());
+    if declared.is_some_and(|len| len > max_bytes) {
+        return too_large();
+    }
+
+    // Content-Length can be absent or wrong, so the body fails to read once
+    // it passes the limit, however the handler consumes it
+    let exceeded = Arc::new(AtomicBool::new(false));
+    let tripped = exceeded.clone();
+    let mut received = 0;
+    let (parts, body) = req.into_parts();
+    let limited = Body::wrap_stream(body.map(move |chunk| {
+        let chunk = chunk.map_err(io::Error::other)?;
+        received += chunk.len();
+        if received > max_bytes {
+            tripped.store(true, Ordering::Relaxed);
+            return Err(io::Error::other("Request body too large"));
+        }
+        Ok(chunk)
+    }));
+
+    let response = inner(hyper::Request::from_parts(parts, limited)).await;
+    if exceeded.load(Ordering::Relaxed) {
+        return too_large();
+    }
+    response
+}
+
+#[derive(Clone, Copy)]
+enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    fn negotiate(accept_encoding: &str) -> Option<Self> {
+        let accepted: Vec<&str> = accept_encoding
+            .split(',')
+            .filter_map(|coding| {
+                let mut parts = coding.trim().split(';');
+                let name = parts.next()?.trim();
+                let refused = parts.any(|param| param.trim().replace(' ', "") == "q=0");
+                (!refused).then_some(name)
+            })
+            .collect();
+
+        if accepted.contains(&"br") {
+            Some(Encoding::Brotli)
+        } else if accepted.contains(&"gzip") {
+            Some(Encoding::Gzip)
+        } else {
+            None
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// Compress a body chunk by chunk as it is sent
+    fn compress(&self, body: Body) -> Body {
+        let reader = StreamReader::new(TryStreamExt::map_err(body, io::Error::other));
+        match self {
+            Encoding::Brotli => Body::wrap_stream(ReaderStream::new(BrotliEncoder::with_quality(
+                reader,
+                Level::Precise(5),
+            ))),
+            Encoding::Gzip => Body::wrap_stream(ReaderStream::new(GzipEncoder::new(reader))),
+        }
+    }
+}
+
+/// Content types that are already compressed, and gain nothing from it
+fn is_compressed(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let Some((kind, subtype)) = essence.split_once('/') else {
+        return false;
+    };
+    match kind {
+        "image" => subtype != "svg+xml" && subtype != "bmp",
+        "audio" | "video" => true,
+        "font" => subtype == "woff" || subtype == "woff2",
+        "application" => matches!(
+            subtype,
+            "zip"
+                | "gzip"
+                | "x-gzip"
+                | "x-bzip2"
+                | "x-xz"
+                | "zstd"
+                | "x-7z-compressed"
+                | "x-rar-compressed"
+                | "vnd.rar"
+                | "pdf"
+                | "wasm"
+        ),
+        _ => false,
+    }
+}
+
+/// Compresses responses with brotli or gzip when the client accepts it
+async fn compress(req: Request, inner: Service, min_bytes: usize) -> Response {
+    let encoding = req
+        .headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Encoding::negotiate);
+
+    let response = inner(req).await;
+    let Some(encoding) = encoding else {
+        return response;
+    };
+    // Already encoded responses are left alone, as are partial ones, whose
+    // Content-Range describes the uncompressed bytes
+    let headers = response.headers();
+    if headers.contains_key(header::CONTENT_ENCODING)
+        || headers.contains_key(header::CONTENT_RANGE)
+        || response.status() == StatusCode::PARTIAL_CONTENT
+    {
+        return response;
+    }
+    let compressed_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(is_compressed);
+    // Bodies of unknown length are streamed, so they are worth compressing
+    let length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok()?.parse::<u64>().ok())
+        .or(response.body().size_hint().exact());
+    if compressed_type || length.is_some_and(|len| len < min_bytes as u64) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.name()),
+    );
+    parts
+        .headers
+        .append(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+    // The encoded bytes differ from the original, so a strong validator no
+    // longer holds for them
+    if let Some(etag) = parts.headers.get(header::ETAG) {
+        if !etag.as_bytes().starts_with(b"W/") {
+            let mut weak = b"W/".to_vec();
+            weak.extend_from_slice(etag.as_bytes());
+            if let Ok(weak) = HeaderValue::from_bytes(&weak) {
+                parts.headers.insert(header::ETAG, weak);
+            }
+        }
+    }
+    Response::from_parts(parts, encoding.compress(body))
+}
+
+/// Gives every request an id, keeping one supplied by the client
+async fn request_id(mut req: Request, inner: Service, header: HeaderName) -> Response {
+    let request_id = match req.headers().get(&header) {
+        Some(value) => value.clone(),
+        None => {
+            let id = HeaderValue::from_str(&Uuid::new_v4().to_string())
+                .expect("uuids are valid header values");
+            req.headers_mut().insert(header.clone(), id.clone());
+            id
+        }
+    };
+
+    let mut response = inner(req).await;
+    response.headers_mut().insert(header, request_id);
+    response
+}
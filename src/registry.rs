@@ -0,0 +1,159 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, warn};
+
+use crate::chain::HashChain;
+use crate::http::HttpHost;
+use crate::mailbox::Mailbox;
+use crate::{ActorInput, ActorMessage, ControlMessage, CONTROL_TIMEOUT};
+
+/// Where messages for a named actor should be delivered
+#[derive(Debug, Clone)]
+pub enum ActorAddress {
+    /// An actor running in this process, reachable through its mailbox
+    Local(Mailbox),
+    /// An actor reachable over HTTP through its `Http` handler
+    Remote(String),
+}
+
+#[derive(Error, Debug)]
+pub enum InspectError {
+    #[error("Unknown actor '{0}'")]
+    UnknownActor(String),
+
+    #[error("Actor '{0}' is not running")]
+    NotRunning(String),
+
+    #[error("Actor '{0}' did not answer in time")]
+    Timeout(String),
+}
+
+/// Resolves logical actor names to local mailboxes or remote endpoints
+pub struct ActorRegistry {
+    entries: RwLock<HashMap<String, ActorAddress>>,
+    /// Control channels of local actors, for inspecting their chains
+    controls: RwLock<HashMap<String, mpsc::Sender<ControlMessage>>>,
+}
+
+impl ActorRegistry {
+    pub fn new() -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            controls: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn register_local(&self, name: &str, mailbox: Mailbox) {
+        info!("[REGISTRY] Registering local actor '{}'", name);
+        self.insert(name, ActorAddress::Local(mailbox));
+    }
+
+    pub fn register_remote(&self, name: &str, url: &str) {
+        info!("[REGISTRY] Registering remote actor '{}' at {}", name, url);
+        self.insert(name, ActorAddress::Remote(url.to_string()));
+    }
+
+    pub fn register_control(&self, name: &str, control_tx: mpsc::Sender<ControlMessage>) {
+        self.controls
+            .write()
+            .unwrap()
+            .insert(name.to_string(), control_tx);
+    }
+
+    pub fn unregister(&self, name: &str) {
+        self.entries.write().unwrap().remove(name);
+        self.controls.write().unwrap().remove(name);
+    }
+
+    /// Run `inspect` against the chain of the local actor registered as
+    /// `name`, once the actor services its control messages. An actor busy
+    /// for longer than the control timeout fails with `Timeout`.
+    pub async fn inspect_chain<T, F>(&self, name: &str, inspect: F) -> Result<T, InspectError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&HashChain) -> T + Send + 'static,
+    {
+        let control_tx = self
+            .controls
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| InspectError::UnknownActor(name.to_string()))?;
+
+        let (response_tx, response_rx) = oneshot::channel();
+        let inspect = Box::new(move |chain: &HashChain| {
+            let _ = response_tx.send(inspect(chain));
+        });
+        let exchange = async {
+            control_tx
+                .send(ControlMessage::InspectChain { inspect })
+                .await
+                .ok()?;
+            response_rx.await.ok()
+        };
+        match tokio::time::timeout(CONTROL_TIMEOUT, exchange).await {
+            Ok(Some(inspected)) => Ok(inspected),
+            Ok(None) => Err(InspectError::NotRunning(name.to_string())),
+            Err(_) => Err(InspectError::Timeout(name.to_string())),
+        }
+    }
+
+    fn insert(&self, name: &str, address: ActorAddress) {
+        let mut entries = self.entries.write().unwrap();
+        if entries.contains_key(name) {
+            warn!("[REGISTRY] Replacing existing entry for actor '{}'", name);
+        }
+        entries.insert(name.to_string(), address);
+    }
+
+    /// Look up an actor by name. Unregistered addresses that look like URLs are
+    /// treated as remote endpoints so existing actors keep working.
+    pub fn resolve(&self, address: &str) -> Option<ActorAddress> {
+        if let Some(entry) = self.entries.read().unwrap().get(address) {
+            return Some(entry.clone());
+        }
+
+        if address.starts_with("http://") || address.starts_with("https://") {
+            return Some(ActorAddress::Remote(address.to_string()));
+        }
+
+        None
+    }
+
+    /// Deliver a message to the actor registered under `address`
+    pub async fn send(&self, address: &str, message: Value, http: Option<&HttpHost>) -> Result<()> {
+        match self.resolve(address) {
+            Some(ActorAddress::Local(mailbox)) => {
+                info!("[REGISTRY] Delivering message to local actor '{}'", address);
+                mailbox
+                    .send(ActorMessage::new(ActorInput::Message(message), None))
+                    .await
+                    .map_err(|e| anyhow!("Failed to deliver to actor '{}': {}", address, e))
+            }
+            Some(ActorAddress::Remote(url)) => match http {
+                Some(http) => http.send_message(url, message).await,
+                None => Err(anyhow!(
+                    "No HTTP host available for sending to remote actor '{}'",
+                    address
+                )),
+            },
+            None => Err(anyhow!("Unknown actor '{}'", address)),
+        }
+    }
+}
+
+impl Default for ActorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Global instance
+lazy_static::lazy_static! {
+    pub static ref ACTOR_REGISTRY: ActorRegistry = ActorRegistry::new();
+}
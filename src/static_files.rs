@@ -0,0 +1,248 @@
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Body, Method, StatusCode};
+use percent_encoding::percent_decode_str;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use tracing::{debug, warn};
+
+use crate::config::StaticFilesConfig;
+use crate::listener::{Request, Response};
+
+/// Answers requests for files under a directory, for the `static_files`
+/// option of the `Http-server` handler
+pub struct StaticFiles {
+    root: PathBuf,
+    index: String,
+}
+
+/// Validators sent with a file, compared against conditional request headers
+struct Version {
+    etag: String,
+    modified: SystemTime,
+}
+
+impl StaticFiles {
+    pub fn new(config: &StaticFilesConfig) -> Self {
+        Self {
+            root: config.dir.clone(),
+            index: config.index.clone(),
+        }
+    }
+
+    /// Answers `req` from the directory, or hands it back when no file matches
+    pub async fn serve(&self, req: Request) -> Result<Response, Request> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return Err(req);
+        }
+        let Some(path) = self.resolve(req.uri().path()) else {
+            return Err(req);
+        };
+        let Some(mut path) = self.contained(&path).await else {
+            return Err(req);
+        };
+        let mut metadata = match tokio::fs::metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(_) => return Err(req),
+        };
+        if metadata.is_dir() {
+            path = match self.contained(&path.join(&self.index)).await {
+                Some(path) => path,
+                None => return Err(req),
+            };
+            metadata = match tokio::fs::metadata(&path).await {
+                Ok(metadata) => metadata,
+                Err(_) => return Err(req),
+            };
+        }
+        if !metadata.is_file() {
+            return Err(req);
+        }
+
+        let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+        let version = Version {
+            etag: etag(metadata.len(), modified),
+            modified,
+        };
+        debug!(
+            "[HTTP] Serving {} from {}",
+            req.uri().path(),
+            path.display()
+        );
+
+        let mut response = if version.not_modified(req.headers()) {
+            status_response(StatusCode::NOT_MODIFIED)
+        } else {
+            let len = metadata.len();
+            let range = match version.range(req.headers(), len) {
+                Ok(range) => range,
+                Err(()) => {
+                    let mut response = status_response(StatusCode::RANGE_NOT_SATISFIABLE);
+                    if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", len)) {
+                        response.headers_mut().insert(header::CONTENT_RANGE, value);
+                    }
+                    return Ok(response);
+                }
+            };
+            let head = req.method() == Method::HEAD;
+            match read(&path, range, len, head).await {
+                Ok(response) => response,
+                Err(e) => {
+                    warn!("[HTTP] Failed to read {}: {}", path.display(), e);
+                    status_response(StatusCode::INTERNAL_SERVER_ERROR)
+                }
+            }
+        };
+
+        let headers = response.headers_mut();
+        if let Ok(value) = HeaderValue::from_str(&version.etag) {
+            headers.insert(header::ETAG, value);
+        }
+        if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(version.modified)) {
+            headers.insert(header::LAST_MODIFIED, value);
+        }
+        headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        if response.status() != StatusCode::NOT_MODIFIED {
+            let mime = mime_guess::from_path(&path).first_or_octet_stream();
+            if let Ok(value) = HeaderValue::from_str(mime.as_ref()) {
+                response.headers_mut().insert(header::CONTENT_TYPE, value);
+            }
+        }
+        Ok(response)
+    }
+
+    /// File named by a request path, refusing paths that would leave the directory
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let mut resolved = self.root.clone();
+        for segment in path.split('/') {
+            let segment = percent_decode_str(segment).decode_utf8().ok()?;
+            match segment.as_ref() {
+                "" | "." => {}
+                ".." => return None,
+                name if name.contains(['/', '\\', '\0']) => return None,
+                name => resolved.push(name),
+            }
+        }
+        Some(resolved)
+    }
+
+    /// `path` with symlinks followed, if it exists and is still inside the directory
+    async fn contained(&self, path: &Path) -> Option<PathBuf> {
+        let root = tokio::fs::canonicalize(&self.root).await.ok()?;
+        let path = tokio::fs::canonicalize(path).await.ok()?;
+        path.starts_with(&root).then_some(path)
+    }
+}
+
+/// Changes whenever a file's size or modification time does
+fn etag(len: u64, modified: SystemTime) -> String {
+    let nanos = modified
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_nanos())
+        .unwrap_or_default();
+    format!("\"{:x}-{:x}\"", len, nanos)
+}
+
+impl Version {
+    /// Whether the client's cached copy is still current; `If-None-Match`
+    /// takes precedence over `If-Modified-Since`
+    fn not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(tags) = headers.get(header::IF_NONE_MATCH) {
+            return tags.to_str().is_ok_and(|tags| self.matches(tags));
+        }
+        headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|since| httpdate::parse_http_date(since.to_str().ok()?).ok())
+            .is_some_and(|since| {
+                // HTTP dates only have whole seconds
+                let modified = self.modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+                let since = since.duration_since(UNIX_EPOCH).unwrap_or_default();
+                modified.as_secs() <= since.as_secs()
+            })
+    }
+
+    fn matches(&self, tags: &str) -> bool {
+        tags.split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == self.etag)
+    }
+
+    /// The single byte range requested, if any. Multiple ranges and ranges for
+    /// an outdated `If-Range` are answered with the whole file.
+    fn range(&self, headers: &HeaderMap, len: u64) -> Result<Option<(u64, u64)>, ()> {
+        let Some(range) = headers.get(header::RANGE).and_then(|r| r.to_str().ok()) else {
+            return Ok(None);
+        };
+        if let Some(if_range) = headers.get(header::IF_RANGE) {
+            if if_range.as_bytes() != self.etag.as_bytes() {
+                return Ok(None);
+            }
+        }
+        let Some(spec) = range.trim().strip_prefix("bytes=") else {
+            return Ok(None);
+        };
+        if spec.contains(',') {
+            return Ok(None);
+        }
+        let Some((start, end)) = spec.trim().split_once('-') else {
+            return Ok(None);
+        };
+
+        let (start, end) = match (start.parse::<u64>(), end.parse::<u64>()) {
+            // bytes=start-end, with the end clamped to the file
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+            // bytes=start-
+            (Ok(start), Err(_)) if end.is_empty() => (start, len.saturating_sub(1)),
+            // bytes=-suffix
+            (Err(_), Ok(suffix)) if start.is_empty() && suffix > 0 => {
+                (len.saturating_sub(suffix), len.saturating_sub(1))
+            }
+            _ => return Ok(None),
+        };
+        if start >= len {
+            return Err(());
+        }
+        Ok(Some((start, end)))
+    }
+}
+
+fn status_response(status: StatusCode) -> Response {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+/// The whole file, or the inclusive byte range `range` of it as a 206,
+/// streamed from disk
+async fn read(
+    path: &Path,
+    range: Option<(u64, u64)>,
+    len: u64,
+    head: bool,
+) -> std::io::Result<Response> {
+    let (status, start, count) = match range {
+        Some((start, end)) => (StatusCode::PARTIAL_CONTENT, start, end - start + 1),
+        None => (StatusCode::OK, 0, len),
+    };
+
+    let body = if head {
+        Body::empty()
+    } else {
+        let mut file = tokio::fs::File::open(path).await?;
+        file.seek(SeekFrom::Start(start)).await?;
+        Body::wrap_stream(ReaderStream::new(file.take(count)))
+    };
+
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(count));
+    if let Some((start, end)) = range {
+        if let Ok(value) = HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, len)) {
+            headers.insert(header::CONTENT_RANGE, value);
+        }
+    }
+    Ok(response)
+}
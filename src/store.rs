@@ -1,12 +1,18 @@
 use crate::http::HttpHost;
+use crate::http_server::RequestBody;
+use crate::mailbox::Mailbox;
+use crate::supervisor::Supervisor;
 use tracing::info;
-use tokio::sync::mpsc;
 
 /// Store type for sharing resources with WASM host functions
 #[derive(Clone)]
 pub struct Store {
     pub http: Option<HttpHost>,
     pub http_server: Option<HttpHost>,
+    pub supervisor: Option<Supervisor>,
+    /// Streamed body of the request being handled, read through the
+    /// `request-body` resource
+    pub request_body: Option<RequestBody>,
 }
 
 impl Store {
@@ -14,29 +20,35 @@ impl Store {
         Self {
             http: None,
             http_server: None,
+            supervisor: None,
+            request_body: None,
         }
     }
 
-    pub fn with_http(port: u16, mailbox_tx: mpsc::Sender<crate::ActorMessage>) -> Self {
+    pub fn with_http(port: u16, mailbox: Mailbox) -> Self {
         info!("[STORE] Initializing store with HTTP handler on port {}", port);
         Self {
-            http: Some(HttpHost::new(mailbox_tx)),
+            http: Some(HttpHost::new(mailbox)),
             http_server: None,
+            supervisor: None,
+            request_body: None,
         }
     }
 
     pub fn with_both_http(
         http_port: u16,
         http_server_port: u16,
-        mailbox_tx: mpsc::Sender<crate::ActorMessage>,
+        mailbox: Mailbox,
     ) -> Self {
         info!(
             "[STORE] Initializing store with HTTP handler on port {} and HTTP server on port {}",
             http_port, http_server_port
         );
         Self {
-            http: Some(HttpHost::new(mailbox_tx.clone())),
-            http_server: Some(HttpHost::new(mailbox_tx)),
+            http: Some(HttpHost::new(mailbox.clone())),
+            http_server: Some(HttpHost::new(mailbox)),
+            supervisor: None,
+            request_body: None,
         }
     }
 
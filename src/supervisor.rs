@@ -0,0 +1,172 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+use crate::chain::ChainEvent;
+use crate::config::{InterfacesConfig, ManifestConfig};
+use crate::mailbox::Mailbox;
+use crate::{ActorInput, ActorMessage, ActorRuntime};
+
+static NEXT_CHILD_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Tracks the child actors spawned by an actor and the chain events that
+/// record those links, so the owning `ActorProcess` can commit them.
+#[derive(Clone)]
+pub struct Supervisor {
+    actor_id: String,
+    mailbox: Mailbox,
+    children: Arc<Mutex<HashMap<String, ActorRuntime>>>,
+    pending_events: Arc<Mutex<Vec<ChainEvent>>>,
+}
+
+impl Supervisor {
+    pub fn new(actor_id: &str, mailbox: Mailbox) -> Self {
+        Self {
+            actor_id: actor_id.to_string(),
+            mailbox,
+            children: Arc::new(Mutex::new(HashMap::new())),
+            pending_events: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn actor_id(&self) -> &str {
+        &self.actor_id
+    }
+
+    /// Start a child actor from a manifest or a component and return its
+    /// actor id. Non-null `init_args` are delivered to the child as its first
+    /// message.
+    pub fn spawn_child(&self, reference: &str, init_args: Value) -> Result<String> {
+        let mut config = child_config(reference)?;
+        let child_id = format!(
+            "{}/{}-{}",
+            self.actor_id,
+            config.name,
+            NEXT_CHILD_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        config.name = child_id.clone();
+
+        info!("[SUPERVISOR] {} spawning child {}", self.actor_id, child_id);
+        let child = ActorRuntime::from_config(config, Some(self))?;
+
+        if !init_args.is_null() {
+            child
+                .mailbox
+                .try_send(ActorMessage::new(ActorInput::Message(init_args), None))
+                .map_err(|e| anyhow!("Failed to deliver init args to {}: {}", child_id, e))?;
+        }
+
+        self.children
+            .lock()
+            .unwrap()
+            .insert(child_id.clone(), child);
+        self.record(ChainEvent::ChildSpawned {
+            child_id: child_id.clone(),
+            manifest: reference.to_string(),
+            timestamp: Utc::now(),
+        });
+
+        Ok(child_id)
+    }
+
+    /// Stop a child actor. The parent is notified once the child has shut down.
+    pub fn stop_child(&self, child_id: &str) -> Result<()> {
+        let mut child = self
+            .children
+            .lock()
+            .unwrap()
+            .remove(child_id)
+            .ok_or_else(|| anyhow!("Unknown child actor '{}'", child_id))?;
+
+        info!("[SUPERVISOR] {} stopping child {}", self.actor_id, child_id);
+        self.record(ChainEvent::ChildStopped {
+            child_id: child_id.to_string(),
+            timestamp: Utc::now(),
+        });
+
+        // The child's process task notifies this actor once it has exited
+        let child_id = child_id.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = child.shutdown().await {
+                error!("[SUPERVISOR] Failed to stop child {}: {}", child_id, e);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Shut down every child, used when the owning actor itself stops
+    pub async fn stop_all(&self) {
+        let children: Vec<_> = self.children.lock().unwrap().drain().collect();
+        for (child_id, mut child) in children {
+            if let Err(e) = Box::pin(child.shutdown()).await {
+                error!("[SUPERVISOR] Failed to stop child {}: {}", child_id, e);
+            }
+        }
+    }
+
+    /// Forget an exited child and deliver a notification to this actor's mailbox
+    pub async fn notify_child_exit(&self, child_id: &str, reason: &str) {
+        // Children that exit on their own are no longer ours to stop
+        self.children.lock().unwrap().remove(child_id);
+
+        let msg = ActorMessage::new(
+            ActorInput::Message(json!({
+                "type": "child-exit",
+                "child": child_id,
+                "reason": reason,
+            })),
+            None,
+        );
+
+        if self.mailbox.send(msg).await.is_err() {
+            error!(
+                "[SUPERVISOR] Could not notify {} that child {} exited",
+                self.actor_id, child_id
+            );
+        }
+    }
+
+    fn record(&self, event: ChainEvent) {
+        self.pending_events.lock().unwrap().push(event);
+    }
+
+    /// Take the lifecycle events recorded since the last call
+    pub fn take_events(&self) -> Vec<ChainEvent> {
+        std::mem::take(&mut *self.pending_events.lock().unwrap())
+    }
+}
+
+/// The manifest at `reference`, or for anything other than a `.toml` file, a
+/// manifest running the component at `reference` as a plain actor
+fn child_config(reference: &str) -> Result<ManifestConfig> {
+    let path = PathBuf::from(reference);
+    if path.extension() == Some(OsStr::new("toml")) {
+        return ManifestConfig::from_file(&path);
+    }
+
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| anyhow!("Invalid component reference '{}'", reference))?
+        .to_string();
+    Ok(ManifestConfig {
+        name,
+        component_path: path,
+        interface: InterfacesConfig {
+            implements: "ntwk:simple-actor/actor".to_string(),
+            requires: Vec::new(),
+        },
+        handlers: Vec::new(),
+        logging: Default::default(),
+        mailbox: Default::default(),
+        metrics: Default::default(),
+        registry: Default::default(),
+    })
+}
@@ -0,0 +1,114 @@
+use anyhow::Result;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use std::sync::Arc;
+use tracing::{info_span, Instrument, Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config::TracingConfig;
+use crate::listener::{Request, Service};
+
+/// Layer exporting spans to the OTLP/HTTP collector in `config`
+pub fn otlp_layer<S>(
+    config: &TracingConfig,
+    actor_name: &str,
+) -> Result<OpenTelemetryLayer<S, Tracer>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&config.otlp_endpoint)
+        .build()?;
+    let service_name = config.service_name.as_deref().unwrap_or(actor_name);
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            service_name.to_string(),
+        )]))
+        .build();
+    Ok(layer(&provider))
+}
+
+/// Layer recording spans with `provider`. Also makes `provider` the global
+/// provider and propagates trace context in W3C `traceparent` headers.
+pub fn layer<S>(provider: &TracerProvider) -> OpenTelemetryLayer<S, Tracer>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+    tracing_opentelemetry::layer().with_tracer(provider.tracer("theater"))
+}
+
+/// Export any spans still buffered, before the process exits
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+/// Span for a request received by a handler, continuing the caller's trace
+/// when the request carries one
+pub fn request_span(handler: &str, req: &Request) -> Span {
+    let span = info_span!(
+        "http.request",
+        otel.kind = "server",
+        handler,
+        http.method = %req.method(),
+        http.target = %req.uri().path(),
+    );
+    let context = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(req.headers()))
+    });
+    span.set_parent(context);
+    span
+}
+
+/// Runs each request `service` handles in a span from [`request_span`]
+pub fn traced(service: Service, handler: &'static str) -> Service {
+    Arc::new(move |req| {
+        let span = request_span(handler, &req);
+        Box::pin(service(req).instrument(span))
+    })
+}
+
+/// Trace context headers identifying `span`, for outgoing requests
+pub fn trace_headers(span: &Span) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&span.context(), &mut HeaderInjector(&mut headers))
+    });
+    headers
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
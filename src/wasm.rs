@@ -1,14 +1,22 @@
 use anyhow::Result;
+use base64::Engine as _;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::time::Instant;
 use thiserror::Error;
-use wasmtime::component::{Component, ComponentExportIndex, Instance, Linker};
-use wasmtime::Engine;
-
-use crate::capabilities::{ActorCapability, BaseActorCapability, HttpCapability};
+use wasmtime::component::{
+    Component, ComponentExportIndex, ComponentType, Instance, Lift, Linker, Lower,
+};
+use wasmtime::{Config, Engine};
+
+use crate::capabilities::{
+    ActorCapability, BaseActorCapability, HttpCapability, TypedHttpCapability,
+    HTTP_ACTOR_INTERFACE, TYPED_HTTP_ACTOR_INTERFACE,
+};
 use crate::config::ManifestConfig;
+use crate::metrics::METRICS;
 use crate::{Actor, ActorInput, ActorOutput, Store};
-use tracing::{error, info};
+use tracing::{error, info, info_span};
 
 #[derive(Error, Debug)]
 pub enum WasmError {
@@ -22,8 +30,112 @@ pub enum WasmError {
     },
 }
 
+/// `method` variant from the typed `http-types` interface
+#[derive(ComponentType, Lower)]
+#[component(variant)]
+enum HttpMethod {
+    #[component(name = "get")]
+    Get,
+    #[component(name = "head")]
+    Head,
+    #[component(name = "post")]
+    Post,
+    #[component(name = "put")]
+    Put,
+    #[component(name = "delete")]
+    Delete,
+    #[component(name = "connect")]
+    Connect,
+    #[component(name = "options")]
+    Options,
+    #[component(name = "trace")]
+    Trace,
+    #[component(name = "patch")]
+    Patch,
+    #[component(name = "other")]
+    Other(String),
+}
+
+impl From<String> for HttpMethod {
+    fn from(method: String) -> Self {
+        match method.to_ascii_uppercase().as_str() {
+            "GET" => HttpMethod::Get,
+            "HEAD" => HttpMethod::Head,
+            "POST" => HttpMethod::Post,
+            "PUT" => HttpMethod::Put,
+            "DELETE" => HttpMethod::Delete,
+            "CONNECT" => HttpMethod::Connect,
+            "OPTIONS" => HttpMethod::Options,
+            "TRACE" => HttpMethod::Trace,
+            "PATCH" => HttpMethod::Patch,
+            _ => HttpMethod::Other(method),
+        }
+    }
+}
+
+/// `http-request` record from the typed `http-types` interface
+#[derive(ComponentType, Lower)]
+#[component(record)]
+struct HttpRequestRecord {
+    method: HttpMethod,
+    uri: String,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+    #[component(name = "peer-addr")]
+    peer_addr: Option<String>,
+    version: Option<String>,
+    params: Vec<(String, String)>,
+    #[component(name = "route-tag")]
+    route_tag: Option<String>,
+    principal: Option<String>,
+}
+
+/// `http-response` record from the typed `http-types` interface
+#[derive(ComponentType, Lift)]
+#[component(record)]
+struct HttpResponseRecord {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+}
+
+/// Decode a JSON response body, given as a UTF-8 string, an array of bytes,
+/// or `{"base64": "..."}`
+fn decode_response_body(body: &Value) -> Result<Vec<u8>, WasmError> {
+    let invalid = |message: String| WasmError::WasmError {
+        context: "response body",
+        message,
+    };
+
+    match body {
+        Value::Null => Ok(Vec::new()),
+        Value::String(text) => Ok(text.as_bytes().to_vec()),
+        Value::Array(bytes) => bytes
+            .iter()
+            .map(|byte| {
+                byte.as_u64()
+                    .and_then(|byte| u8::try_from(byte).ok())
+                    .ok_or_else(|| invalid(format!("{} is not a byte", byte)))
+            })
+            .collect(),
+        Value::Object(fields) => match fields.get("base64").and_then(|b| b.as_str()) {
+            Some(encoded) => base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|e| invalid(format!("Invalid base64 body: {}", e))),
+            None => Err(invalid("Expected a \"base64\" field".to_string())),
+        },
+        other => Err(invalid(format!("Unsupported body type: {}", other))),
+    }
+}
+
 /// Implementation of the Actor trait for WebAssembly components
 pub struct WasmActor {
+    /// Actor name, labelling this component's metrics
+    name: String,
+    /// Whether calls are metered for fuel
+    meter_fuel: bool,
     engine: Engine,
     component: Component,
     linker: Linker<Store>,
@@ -34,8 +146,10 @@ pub struct WasmActor {
 
 impl WasmActor {
     pub fn new(config: &ManifestConfig, store: Store) -> Result<Self> {
-        // Load WASM component
-        let engine = Engine::default();
+        // Load WASM component, metering fuel only when its metric is wanted
+        let mut engine_config = Config::new();
+        engine_config.consume_fuel(config.metrics.fuel);
+        let engine = Engine::new(&engine_config)?;
         let wasm_bytes = std::fs::read(&config.component_path).map_err(|e| WasmError::WasmError {
             context: "component loading",
             message: format!("Failed to load WASM component from {}: {}", config.component_path.display(), e),
@@ -44,6 +158,8 @@ impl WasmActor {
         let linker = Linker::new(&engine);
 
         let mut actor = WasmActor {
+            name: config.name.clone(),
+            meter_fuel: config.metrics.fuel,
             engine,
             component,
             linker,
@@ -56,10 +172,14 @@ impl WasmActor {
             actor.add_capability(Box::new(BaseActorCapability))?;
         }
 
-        if config.implements_interface("ntwk:simple-http-actor/http-actor") {
+        if config.implements_interface(HTTP_ACTOR_INTERFACE) {
             actor.add_capability(Box::new(HttpCapability))?;
         }
 
+        if config.implements_interface(TYPED_HTTP_ACTOR_INTERFACE) {
+            actor.add_capability(Box::new(TypedHttpCapability))?;
+        }
+
         Ok(actor)
     }
 
@@ -77,10 +197,32 @@ impl WasmActor {
         Ok(())
     }
 
+    fn has_capability(&self, interface_name: &str) -> bool {
+        self.capabilities
+            .iter()
+            .any(|capability| capability.interface_name() == interface_name)
+    }
+
     fn get_export(&self, name: &str) -> Option<&ComponentExportIndex> {
         self.exports.get(name)
     }
 
+    /// A fresh store and instance for one call, recording how long
+    /// instantiation took
+    fn instantiate(&self) -> Result<(wasmtime::Store<Store>, Instance)> {
+        let mut store = wasmtime::Store::new(&self.engine, self.store.clone());
+        if self.meter_fuel {
+            store.set_fuel(u64::MAX)?;
+        }
+        let started = Instant::now();
+        let instance = self.linker.instantiate(&mut store, &self.component)?;
+        METRICS.wasm_instantiation.observe(
+            &[("actor", self.name.as_str())],
+            started.elapsed().as_secs_f64(),
+        );
+        Ok((store, instance))
+    }
+
     fn call_func<T, U>(
         &self,
         store: &mut wasmtime::Store<Store>,
@@ -92,6 +234,8 @@ impl WasmActor {
         T: wasmtime::component::Lower + wasmtime::component::ComponentNamedList,
         U: wasmtime::component::Lift + wasmtime::component::ComponentNamedList,
     {
+        let _span = info_span!("wasm.call", actor = %self.name, export = export_name).entered();
+
         let index = self
             .get_export(export_name)
             .ok_or_else(|| WasmError::WasmError {
@@ -113,19 +257,30 @@ impl WasmActor {
                 message: e.to_string(),
             })?;
 
-        Ok(typed
-            .call(&mut *store, args)
-            .map_err(|e| WasmError::WasmError {
-                context: "function call",
-                message: e.to_string(),
-            })?)
+        let fuel = if self.meter_fuel {
+            Some(store.get_fuel()?)
+        } else {
+            None
+        };
+        let result = typed.call(&mut *store, args);
+        if let Some(fuel) = fuel {
+            let consumed = fuel.saturating_sub(store.get_fuel()?);
+            METRICS.fuel_consumed.inc_by(
+                &[("actor", self.name.as_str()), ("export", export_name)],
+                consumed,
+            );
+        }
+
+        Ok(result.map_err(|e| WasmError::WasmError {
+            context: "function call",
+            message: e.to_string(),
+        })?)
     }
 }
 
 impl Actor for WasmActor {
     fn init(&self) -> Result<Value> {
-        let mut store = wasmtime::Store::new(&self.engine, self.store.clone());
-        let instance = self.linker.instantiate(&mut store, &self.component)?;
+        let (mut store, instance) = self.instantiate()?;
 
         let (result,) = self.call_func::<(), (Vec<u8>,)>(&mut store, &instance, "init", ())?;
         let state: Value = serde_json::from_slice(&result)?;
@@ -134,8 +289,7 @@ impl Actor for WasmActor {
     }
 
     fn handle_input(&self, input: ActorInput, state: &Value) -> Result<(ActorOutput, Value)> {
-        let mut store = wasmtime::Store::new(&self.engine, self.store.clone());
-        let instance = self.linker.instantiate(&mut store, &self.component)?;
+        let (mut store, instance) = self.instantiate()?;
 
         let state_bytes = serde_json::to_vec(state)?;
 
@@ -156,21 +310,73 @@ impl Actor for WasmActor {
             ActorInput::HttpRequest {
                 method,
                 uri,
+                path,
+                query,
+                params,
                 headers,
                 body,
+                body_stream,
+                peer_addr,
+                version,
+                route_tag,
+                principal,
             } => {
                 if !self.exports.contains_key("handle-http") {
                     return Err(anyhow::anyhow!("Actor does not support HTTP"));
                 }
+                store.data_mut().request_body = body_stream;
+
+                info!("[HTTP] Received request: {} {}", method, uri);
+
+                if self.has_capability(TYPED_HTTP_ACTOR_INTERFACE) {
+                    let request = HttpRequestRecord {
+                        method: method.into(),
+                        uri,
+                        path,
+                        query,
+                        headers,
+                        body,
+                        peer_addr,
+                        version,
+                        params,
+                        route_tag,
+                        principal,
+                    };
+                    let ((response, new_state),) = self.call_func::<
+                        (HttpRequestRecord, Vec<u8>),
+                        ((HttpResponseRecord, Vec<u8>),),
+                    >(
+                        &mut store,
+                        &instance,
+                        "handle-http",
+                        (request, state_bytes),
+                    )?;
+                    info!("[HTTP] Response status: {}", response.status);
+
+                    return Ok((
+                        ActorOutput::HttpResponse {
+                            status: response.status,
+                            headers: response.headers,
+                            body: response.body,
+                        },
+                        serde_json::from_slice(&new_state)?,
+                    ));
+                }
 
                 let request = serde_json::json!({
                     "method": method,
                     "uri": uri,
+                    "path": path,
+                    "query": query,
+                    "params": params,
                     "headers": { "fields": headers },
                     "body": body,
+                    "peer-addr": peer_addr,
+                    "version": version,
+                    "route-tag": route_tag,
+                    "principal": principal,
                 });
 
-                info!("[HTTP] Received request: {} {}", method, uri);
                 let request_bytes = serde_json::to_vec(&request)?;
                 let (result,) = self.call_func::<(Vec<u8>, Vec<u8>), (Vec<u8>,)>(
                     &mut store,
@@ -197,10 +403,7 @@ impl Actor for WasmActor {
                     })
                     .unwrap_or_default();
 
-                let body = http_response["body"]
-                    .as_str()
-                    .map(|s| s.as_bytes().to_vec())
-                    .unwrap_or_default();
+                let body = decode_response_body(&http_response["body"])?;
 
                 Ok((
                     ActorOutput::HttpResponse {
@@ -215,9 +418,8 @@ impl Actor for WasmActor {
     }
 
     fn verify_state(&self, state: &Value) -> bool {
-        let mut store = wasmtime::Store::new(&self.engine, self.store.clone());
-        let instance = match self.linker.instantiate(&mut store, &self.component) {
-            Ok(instance) => instance,
+        let (mut store, instance) = match self.instantiate() {
+            Ok(instantiated) => instantiated,
             Err(_) => return false,
         };
 
@@ -226,13 +428,20 @@ impl Actor for WasmActor {
             Err(_) => return false,
         };
 
-        self.call_func::<(Vec<u8>,), (bool,)>(
-            &mut store,
-            &instance,
-            "state-contract",
-            (state_bytes,),
-        )
-        .map(|(result,)| result)
-        .unwrap_or(false)
+        let valid = self
+            .call_func::<(Vec<u8>,), (bool,)>(
+                &mut store,
+                &instance,
+                "state-contract",
+                (state_bytes,),
+            )
+            .map(|(result,)| result)
+            .unwrap_or(false);
+        if !valid {
+            METRICS
+                .contract_rejections
+                .inc(&[("actor", self.name.as_str())]);
+        }
+        valid
     }
 }
@@ -0,0 +1,285 @@
+mod common;
+
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use jsonwebtoken::{EncodingKey, Header};
+use serde_json::json;
+use sha2::Sha256;
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tempfile::NamedTempFile;
+use theater::auth::{
+    hmac_signing_payload, AuthError, AuthRequest, Authenticator, HMAC_KEY_ID_HEADER,
+    HMAC_SIGNATURE_HEADER, HMAC_TIMESTAMP_HEADER,
+};
+use theater::chain::ChainEvent;
+use theater::config::{
+    AuthConfig, BearerToken, HmacKey, HttpHandlerConfig, HttpServerHandlerConfig,
+};
+use theater::http::HttpHandler;
+use theater::http_server::HttpServerHandler;
+use theater::{Actor, ActorInput, ActorMessage, ActorOutput, ActorProcess, MessageMetadata};
+use tokio::sync::mpsc;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn request<'a>(headers: &'a [(String, String)]) -> AuthRequest<'a> {
+    AuthRequest {
+        method: "GET",
+        target: "/",
+        headers,
+        body: b"",
+    }
+}
+
+fn bearer(token: &str) -> Vec<(String, String)> {
+    vec![("Authorization".to_string(), format!("Bearer {}", token))]
+}
+
+#[tokio::test]
+async fn test_bearer_auth_on_http_server() -> Result<()> {
+    let handler = HttpServerHandler::from_config(HttpServerHandlerConfig {
+        port: 18097,
+        timeout_ms: 1000,
+        auth: Some(AuthConfig::Bearer {
+            tokens: vec![BearerToken {
+                token: "s3cret".to_string(),
+                principal: "dashboard".to_string(),
+            }],
+        }),
+        ..Default::default()
+    });
+
+    common::start_echo(handler).await?;
+
+    let client = reqwest::Client::new();
+    let response = client.get("http://127.0.0.1:18097/").send().await?;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(response.headers()["www-authenticate"], "Bearer");
+
+    // Callers without credentials are turned away before their body is read
+    let never_ends = futures::stream::pending::<Result<&'static [u8], std::io::Error>>();
+    let request = hyper::Request::post("http://127.0.0.1:18097/")
+        .body(hyper::Body::wrap_stream(never_ends))?;
+    let response = tokio::time::timeout(
+        Duration::from_secs(2),
+        hyper::Client::new().request(request),
+    )
+    .await??;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = client
+        .get("http://127.0.0.1:18097/")
+        .bearer_auth("guess")
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = client
+        .get("http://127.0.0.1:18097/")
+        .bearer_auth("s3cret")
+        .send()
+        .await?;
+    match response.json::<ActorInput>().await? {
+        ActorInput::HttpRequest { principal, .. } => {
+            assert_eq!(principal.as_deref(), Some("dashboard"))
+        }
+        other => panic!("Unexpected input: {:?}", other),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_hmac_auth_on_http_handler() -> Result<()> {
+    let handler = HttpHandler::from_config(HttpHandlerConfig {
+        port: 18098,
+        address: "127.0.0.1".to_string(),
+        path_prefix: None,
+        tls: None,
+        auth: Some(AuthConfig::Hmac {
+            keys: vec![HmacKey {
+                key_id: "billing".to_string(),
+                secret: "shared-secret".to_string(),
+                principal: None,
+            }],
+            max_skew_secs: 60,
+        }),
+    });
+
+    let (tx, mut rx) = mpsc::channel(32);
+    common::start(handler, tx).await?;
+
+    let body = serde_json::to_vec(&json!({"action": "charge"}))?;
+    let sign = |timestamp: u64, secret: &str| {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(&hmac_signing_payload(timestamp, "POST", "/", &body));
+        hex::encode(mac.finalize().into_bytes())
+    };
+    let post = |timestamp: u64, signature: String| {
+        reqwest::Client::new()
+            .post("http://127.0.0.1:18098/")
+            .header(HMAC_KEY_ID_HEADER, "billing")
+            .header(HMAC_TIMESTAMP_HEADER, timestamp.to_string())
+            .header(HMAC_SIGNATURE_HEADER, signature)
+            .body(body.clone())
+            .send()
+    };
+
+    let response = post(now(), sign(now(), "wrong-secret")).await?;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let stale = now() - 3600;
+    let response = post(stale, sign(stale, "shared-secret")).await?;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let timestamp = now();
+    let response = post(timestamp, sign(timestamp, "shared-secret")).await?;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Only the signed message was delivered, along with its sender
+    let msg = rx.recv().await.unwrap();
+    assert!(matches!(
+        msg.metadata,
+        Some(MessageMetadata::Authenticated { ref principal }) if principal == "billing"
+    ));
+    match msg.content {
+        ActorInput::Message(content) => assert_eq!(
+            content,
+            json!({ "principal": "billing", "message": { "action": "charge" } })
+        ),
+        other => panic!("Unexpected input: {:?}", other),
+    }
+    assert!(rx.try_recv().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_jwt_auth_with_local_jwks() -> Result<()> {
+    let secret = b"jwt-signing-secret";
+    let mut jwks = NamedTempFile::new()?;
+    write!(
+        jwks,
+        "{}",
+        json!({
+            "keys": [{
+                "kty": "oct",
+                "kid": "primary",
+                "alg": "HS256",
+                "k": base64::Engine::encode(
+                    &base64::engine::general_purpose::URL_SAFE_NO_PAD,
+                    secret
+                ),
+            }]
+        })
+    )?;
+
+    let auth = Authenticator::new(&AuthConfig::Jwt {
+        jwks_path: jwks.path().to_path_buf(),
+        issuer: Some("https://auth.example.com".to_string()),
+        audience: None,
+        algorithms: vec![],
+    })?;
+
+    let token = |kid: &str, claims: serde_json::Value| {
+        let mut header = Header::new(jsonwebtoken::Algorithm::HS256);
+        header.kid = Some(kid.to_string());
+        jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(secret)).unwrap()
+    };
+    let exp = now() + 600;
+
+    let valid = token(
+        "primary",
+        json!({"sub": "alice", "iss": "https://auth.example.com", "exp": exp}),
+    );
+    assert_eq!(auth.authenticate(&request(&bearer(&valid)))?, "alice");
+
+    let wrong_issuer = token(
+        "primary",
+        json!({"sub": "alice", "iss": "https://evil.example.com", "exp": exp}),
+    );
+    let expired = token(
+        "primary",
+        json!({"sub": "alice", "iss": "https://auth.example.com", "exp": now() - 600}),
+    );
+    let unknown_key = token(
+        "rotated",
+        json!({"sub": "alice", "iss": "https://auth.example.com", "exp": exp}),
+    );
+    // The key is for HS256, so a token declaring another algorithm is refused
+    let mut header = Header::new(jsonwebtoken::Algorithm::HS384);
+    header.kid = Some("primary".to_string());
+    let wrong_algorithm = jsonwebtoken::encode(
+        &header,
+        &json!({"sub": "alice", "iss": "https://auth.example.com", "exp": exp}),
+        &EncodingKey::from_secret(secret),
+    )?;
+    for rejected in [wrong_issuer, expired, unknown_key, wrong_algorithm] {
+        assert!(matches!(
+            auth.authenticate(&request(&bearer(&rejected))),
+            Err(AuthError::InvalidCredentials(_))
+        ));
+    }
+    assert!(matches!(
+        auth.authenticate(&request(&[])),
+        Err(AuthError::MissingCredentials)
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_principal_recorded_in_chain() -> Result<()> {
+    struct EchoActor;
+
+    impl Actor for EchoActor {
+        fn init(&self) -> Result<serde_json::Value> {
+            Ok(json!({}))
+        }
+
+        fn handle_input(
+            &self,
+            input: ActorInput,
+            state: &serde_json::Value,
+        ) -> Result<(ActorOutput, serde_json::Value)> {
+            match input {
+                ActorInput::Message(msg) => Ok((ActorOutput::Message(msg), state.clone())),
+                _ => Err(anyhow::anyhow!("unsupported input")),
+            }
+        }
+
+        fn verify_state(&self, _state: &serde_json::Value) -> bool {
+            true
+        }
+    }
+
+    let (tx, rx) = mpsc::channel(32);
+    let mut process = ActorProcess::new(&"echo".to_string(), Box::new(EchoActor), rx)?;
+    tx.send(ActorMessage::new(
+        ActorInput::Message(json!({"action": "charge"})),
+        Some(MessageMetadata::Authenticated {
+            principal: "billing".to_string(),
+        }),
+    ))
+    .await?;
+    drop(tx);
+
+    process.run().await?;
+
+    assert!(process
+        .get_chain()
+        .get_full_chain()
+        .iter()
+        .any(|(_, entry)| matches!(
+            &entry.event,
+            ChainEvent::ExternalInput { principal: Some(p), .. } if p == "billing"
+        )));
+
+    Ok(())
+}
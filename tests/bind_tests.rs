@@ -0,0 +1,184 @@
+mod common;
+
+use anyhow::Result;
+use serde_json::json;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use tempfile::TempDir;
+use theater::config::{BindAddress, HttpServerHandlerConfig, TlsConfig};
+use theater::http::HttpHandler;
+use theater::http_server::HttpServerHandler;
+use theater::listener::Mount;
+use theater::{ActorInput, ActorOutput, HostHandler};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+
+#[test]
+fn test_bind_address_parsing() -> Result<()> {
+    assert_eq!(
+        BindAddress::parse("0.0.0.0", 8080)?,
+        BindAddress::Tcp("0.0.0.0:8080".parse::<SocketAddr>()?)
+    );
+    for ipv6 in ["::1", "[::1]"] {
+        assert_eq!(
+            BindAddress::parse(ipv6, 8080)?,
+            BindAddress::Tcp("[::1]:8080".parse::<SocketAddr>()?)
+        );
+    }
+    assert_eq!(
+        BindAddress::parse("unix:/run/theater/actor.sock", 8080)?,
+        BindAddress::Unix(PathBuf::from("/run/theater/actor.sock"))
+    );
+    assert!(BindAddress::parse("not a host", 8080).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bind_failure_is_reported() -> Result<()> {
+    let _taken = std::net::TcpListener::bind("127.0.0.1:18101")?;
+
+    let error = HttpHandler::new(18101).bind().unwrap_err();
+    assert!(error.to_string().contains("Failed to bind 127.0.0.1:18101"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_handlers_share_a_port_by_path_prefix() -> Result<()> {
+    let server = HttpServerHandler::from_config(serde_json::from_value(json!({
+        "port": 18102,
+        "path_prefix": "/api",
+    }))?);
+    let messages = HttpHandler::from_config(serde_json::from_value(json!({
+        "port": 18102,
+        "path_prefix": "/messages",
+    }))?);
+    common::start_echo(server).await?;
+    let (messages_tx, mut messages_rx) = mpsc::channel(32);
+    common::start(messages, messages_tx).await?;
+
+    // A prefix can only be served once per address
+    let duplicate = HttpServerHandler::from_config(serde_json::from_value(json!({
+        "port": 18102,
+        "path_prefix": "/api/",
+    }))?);
+    assert!(duplicate.bind().is_err());
+
+    let events = Mount::bind(&BindAddress::parse("127.0.0.1", 18102)?, None, "/")?;
+    events.serve(theater::event_server::service());
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get("http://127.0.0.1:18102/api/users?id=7")
+        .send()
+        .await?;
+    match response.json::<ActorInput>().await? {
+        ActorInput::HttpRequest { uri, path, .. } => {
+            assert_eq!(uri, "http://127.0.0.1:18102/api/users?id=7");
+            assert_eq!(path, "/users");
+        }
+        other => panic!("Unexpected input: {:?}", other),
+    }
+
+    let response = client
+        .post("http://127.0.0.1:18102/messages")
+        .json(&json!({"action": "ping"}))
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 200);
+    match messages_rx.recv().await.unwrap().content {
+        ActorInput::Message(payload) => assert_eq!(payload, json!({"action": "ping"})),
+        other => panic!("Unexpected input: {:?}", other),
+    }
+
+    // Everything else falls through to the event server mounted at the root
+    let response = client
+        .get("http://127.0.0.1:18102/events/history")
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.json::<serde_json::Value>().await?.is_array());
+    let response = client.get("http://127.0.0.1:18102/apix").send().await?;
+    assert_eq!(response.status().as_u16(), 404);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_server_on_unix_socket() -> Result<()> {
+    let dir = TempDir::new()?;
+    let socket = dir.path().join("actor.sock");
+    let config: HttpServerHandlerConfig = serde_json::from_value(json!({
+        "port": 0,
+        "address": format!("unix:{}", socket.display()),
+    }))?;
+    common::start_actor(HttpServerHandler::from_config(config), |_| {
+        ActorOutput::HttpResponse {
+            status: 200,
+            headers: vec![],
+            body: Some(b"over a unix socket".to_vec()),
+        }
+    })
+    .await?;
+
+    let mut stream = UnixStream::connect(&socket).await?;
+    stream
+        .write_all(b"GET /status HTTP/1.1\r\nHost: actor\r\nConnection: close\r\n\r\n")
+        .await?;
+    let mut response = String::new();
+    tokio::time::timeout(Duration::from_secs(1), stream.read_to_string(&mut response)).await??;
+
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("over a unix socket"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unix_socket_binds_twice_in_a_row() -> Result<()> {
+    let dir = TempDir::new()?;
+    let socket = dir.path().join("actor.sock");
+    let address = BindAddress::Unix(socket.clone());
+
+    // The socket file goes away with the last mount, so the path can be reused
+    let mount = Mount::bind(&address, None, "/")?;
+    assert!(socket.exists());
+    drop(mount);
+    assert!(!socket.exists());
+    let mount = Mount::bind(&address, None, "/")?;
+    drop(mount);
+
+    // A socket left behind by a server that is gone is replaced
+    drop(std::os::unix::net::UnixListener::bind(&socket)?);
+    assert!(socket.exists());
+    let _mount = Mount::bind(&address, None, "/")?;
+    UnixStream::connect(&socket).await?;
+
+    // Anything that isn't a socket is left alone
+    let file = dir.path().join("notes.txt");
+    std::fs::write(&file, "keep me")?;
+    assert!(Mount::bind(&BindAddress::Unix(file.clone()), None, "/").is_err());
+    assert_eq!(std::fs::read_to_string(&file)?, "keep me");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_shared_address_requires_same_tls() -> Result<()> {
+    let address = BindAddress::parse("127.0.0.1", 18108)?;
+    let _plain = Mount::bind(&address, None, "/api")?;
+
+    // A handler asking for mTLS must not be served in plaintext
+    let tls = TlsConfig {
+        cert_path: PathBuf::from("certs/server.pem"),
+        key_path: PathBuf::from("certs/server.key"),
+        client_ca_path: Some(PathBuf::from("certs/ca.pem")),
+    };
+    assert!(Mount::bind(&address, Some(&tls), "/secure").is_err());
+    assert!(Mount::bind(&address, None, "/other").is_ok());
+
+    Ok(())
+}
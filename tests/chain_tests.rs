@@ -8,15 +8,23 @@ use theater::{ActorInput, ActorOutput};
 
 #[test]
 fn test_error_handling() {
-    let mut chain = HashChain::new();
+    let mut chain = HashChain::new("test");
 
     // Initial request
     chain.add_event(ChainEvent::Input {
         input: ActorInput::HttpRequest {
             method: "GET".to_string(),
             uri: "/api/users/invalid-id".to_string(),
+            path: "/api/users/invalid-id".to_string(),
+            query: vec![],
+            params: vec![],
             headers: vec![],
             body: None,
+            body_stream: None,
+            peer_addr: None,
+            version: None,
+            route_tag: None,
+            principal: None,
         },
         timestamp: Utc::now(),
     });
@@ -96,7 +104,7 @@ fn test_error_handling() {
 
 #[test]
 fn test_interleaved_requests() {
-    let mut chain = HashChain::new();
+    let mut chain = HashChain::new("test");
 
     // Request 1 starts
     chain.add_event(ChainEvent::Input {
@@ -170,7 +178,7 @@ fn test_interleaved_requests() {
 
 #[test]
 fn test_state_rollback() {
-    let mut chain = HashChain::new();
+    let mut chain = HashChain::new("test");
 
     // Initial state
     chain.add_event(ChainEvent::StateChange {
@@ -211,7 +219,7 @@ fn test_state_rollback() {
 
 #[test]
 fn test_chain_integrity() {
-    let mut chain = HashChain::new();
+    let mut chain = HashChain::new("test");
 
     // Add a sequence of events
     let hash1 = chain.add_event(ChainEvent::StateChange {
@@ -0,0 +1,67 @@
+//! Fixtures shared by the integration tests
+#![allow(dead_code)]
+
+use anyhow::Result;
+use std::sync::Arc;
+use std::task::Poll;
+use theater::mailbox::Mailbox;
+use theater::{ActorInput, ActorOutput, HostHandler, MessageMetadata};
+use tokio::sync::mpsc;
+
+/// Bind `handler` and start it delivering requests to `mailbox`. Requests are
+/// answered as soon as this returns.
+pub async fn start<H>(handler: H, mailbox: impl Into<Mailbox>) -> Result<Arc<H>>
+where
+    H: HostHandler + 'static,
+{
+    let handler = Arc::new(handler);
+    handler.bind()?;
+
+    // Handlers mount their service before waiting to be stopped, so it is in
+    // place once the first poll returns
+    let started = handler.clone();
+    let mailbox = mailbox.into();
+    let mut serving = Box::pin(async move { started.start(mailbox).await });
+    match futures::poll!(&mut serving) {
+        Poll::Ready(result) => result?,
+        Poll::Pending => {
+            tokio::spawn(serving);
+        }
+    }
+    Ok(handler)
+}
+
+/// Start `handler` with an actor that answers every request with `respond`
+pub async fn start_actor<H, F>(handler: H, respond: F) -> Result<Arc<H>>
+where
+    H: HostHandler + 'static,
+    F: Fn(ActorInput) -> ActorOutput + Send + 'static,
+{
+    let (tx, mut rx) = mpsc::channel(32);
+    let handler = start(handler, tx).await?;
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if let Some(MessageMetadata::HttpRequest {
+                response_channel, ..
+            }) = msg.metadata
+            {
+                let _ = response_channel.send(respond(msg.content));
+            }
+        }
+    });
+    Ok(handler)
+}
+
+/// Start `handler` with an actor that echoes the request it received back
+/// to the caller, as JSON
+pub async fn start_echo<H>(handler: H) -> Result<Arc<H>>
+where
+    H: HostHandler + 'static,
+{
+    start_actor(handler, |input| ActorOutput::HttpResponse {
+        status: 200,
+        headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+        body: Some(serde_json::to_vec(&input).unwrap()),
+    })
+    .await
+}
@@ -1,6 +1,9 @@
 use std::io::Write;
+use std::path::PathBuf;
 use tempfile::NamedTempFile;
-use theater::config::ManifestConfig;
+use theater::config::{
+    LogFormat, LogOutput, ManifestConfig, MiddlewareConfig, DEFAULT_MAX_BODY_BYTES,
+};
 
 #[test]
 fn test_manifest_loading() {
@@ -72,3 +75,295 @@ config = { port = 8080 }
     assert!(!config.implements_interface("ntwk:simple-actor/actor"));
     assert_eq!(config.interface(), "ntwk:simple-http-actor/http-actor");
 }
+
+#[test]
+fn test_registry_config() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+
+    let manifest_content = r#"
+name = "frontend"
+component_path = "test.wasm"
+
+[interface]
+implements = "ntwk:simple-actor/actor"
+requires = []
+
+[registry]
+counter = "http://10.0.0.5:8080"
+"#;
+
+    write!(temp_file, "{}", manifest_content).unwrap();
+
+    let config = ManifestConfig::from_file(temp_file.path()).unwrap();
+    assert_eq!(
+        config.registry.get("counter").map(String::as_str),
+        Some("http://10.0.0.5:8080")
+    );
+}
+
+#[test]
+fn test_mailbox_config() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+
+    let manifest_content = r#"
+name = "busy-actor"
+component_path = "test.wasm"
+
+[interface]
+implements = "ntwk:simple-actor/actor"
+requires = []
+
+[mailbox]
+capacity = 128
+overflow = "drop-oldest"
+"#;
+
+    write!(temp_file, "{}", manifest_content).unwrap();
+
+    let config = ManifestConfig::from_file(temp_file.path()).unwrap();
+    assert_eq!(config.mailbox.capacity, 128);
+    assert!(matches!(
+        config.mailbox.overflow,
+        theater::config::OverflowPolicy::DropOldest
+    ));
+    assert_eq!(config.mailbox.reject_status, 503);
+}
+
+#[test]
+fn test_http_server_timeouts_config() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+
+    let manifest_content = r#"
+name = "slow-actor"
+component_path = "test.wasm"
+
+[interface]
+implements = "ntwk:simple-http-actor/http-actor"
+requires = []
+
+[[handlers]]
+type = "Http-server"
+config = { port = 8081, timeout_ms = 5000, route_timeouts = [
+    { path = "/reports", timeout_ms = 120000 },
+] }
+"#;
+
+    write!(temp_file, "{}", manifest_content).unwrap();
+
+    let config = ManifestConfig::from_file(temp_file.path()).unwrap();
+    match &config.handlers[0] {
+        theater::HandlerConfig::HttpServer(http_config) => {
+            assert_eq!(http_config.timeout_for("/"), 5000);
+            assert_eq!(http_config.timeout_for("/reports/2024"), 120000);
+            // Bodies are capped unless the manifest says otherwise
+            assert_eq!(http_config.max_body_bytes, Some(DEFAULT_MAX_BODY_BYTES));
+        }
+        other => panic!("Unexpected handler: {:?}", other),
+    }
+}
+
+#[test]
+fn test_http_server_routes_config() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+
+    let manifest_content = r#"
+name = "users-actor"
+component_path = "test.wasm"
+
+[interface]
+implements = "ntwk:simple-http-actor/http-actor"
+requires = []
+
+[[handlers]]
+type = "Http-server"
+config = { port = 8081, routes = [
+    { method = "GET", path = "/users/:id", tag = "get-user" },
+    { path = "/static/*file" },
+] }
+"#;
+
+    write!(temp_file, "{}", manifest_content).unwrap();
+
+    let config = ManifestConfig::from_file(temp_file.path()).unwrap();
+    match &config.handlers[0] {
+        theater::HandlerConfig::HttpServer(http_config) => {
+            assert_eq!(http_config.routes.len(), 2);
+            assert_eq!(http_config.routes[0].method.as_deref(), Some("GET"));
+            assert_eq!(http_config.routes[0].tag.as_deref(), Some("get-user"));
+            assert_eq!(http_config.routes[1].method, None);
+        }
+        other => panic!("Unexpected handler: {:?}", other),
+    }
+}
+
+#[test]
+fn test_http_server_middleware_config() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+
+    let manifest_content = r#"
+name = "api-actor"
+component_path = "test.wasm"
+
+[interface]
+implements = "ntwk:simple-http-actor/http-actor"
+requires = []
+
+[[handlers]]
+type = "Http-server"
+config = { port = 8081, middleware = [
+    { type = "cors", allow_origins = ["https://app.example.com"] },
+    { type = "request-id" },
+    { type = "logging" },
+    { type = "body-limit", max_bytes = 1048576 },
+    { type = "compression" },
+] }
+"#;
+
+    write!(temp_file, "{}", manifest_content).unwrap();
+
+    let config = ManifestConfig::from_file(temp_file.path()).unwrap();
+    match &config.handlers[0] {
+        theater::HandlerConfig::HttpServer(http_config) => {
+            assert_eq!(http_config.middleware.len(), 5);
+            assert!(matches!(
+                &http_config.middleware[0],
+                MiddlewareConfig::Cors { allow_origins, max_age_secs: 86400, .. }
+                    if allow_origins == &["https://app.example.com"]
+            ));
+            assert!(matches!(
+                &http_config.middleware[1],
+                MiddlewareConfig::RequestId { header } if header == "X-Request-Id"
+            ));
+            assert!(matches!(
+                http_config.middleware[3],
+                MiddlewareConfig::BodyLimit {
+                    max_bytes: 1048576
+                }
+            ));
+            assert!(matches!(
+                http_config.middleware[4],
+                MiddlewareConfig::Compression { min_bytes: 1024 }
+            ));
+        }
+        other => panic!("Unexpected handler: {:?}", other),
+    }
+}
+
+#[test]
+fn test_handler_tls_config() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+
+    let manifest_content = r#"
+name = "edge-actor"
+component_path = "test.wasm"
+
+[interface]
+implements = "ntwk:simple-http-actor/http-actor"
+requires = []
+
+[[handlers]]
+type = "Http"
+config = { port = 8080 }
+
+[[handlers]]
+type = "Http-server"
+config = { port = 8443, address = "0.0.0.0", tls = { cert_path = "certs/server.pem", key_path = "certs/server.key", client_ca_path = "certs/ca.pem" } }
+"#;
+
+    write!(temp_file, "{}", manifest_content).unwrap();
+
+    let config = ManifestConfig::from_file(temp_file.path()).unwrap();
+    match &config.handlers[0] {
+        theater::HandlerConfig::Http(http_config) => {
+            assert_eq!(http_config.address, "127.0.0.1");
+            assert!(http_config.tls.is_none());
+        }
+        other => panic!("Unexpected handler: {:?}", other),
+    }
+    match &config.handlers[1] {
+        theater::HandlerConfig::HttpServer(http_config) => {
+            assert_eq!(http_config.address, "0.0.0.0");
+            let tls = http_config.tls.as_ref().unwrap();
+            assert_eq!(tls.cert_path, PathBuf::from("certs/server.pem"));
+            assert_eq!(tls.key_path, PathBuf::from("certs/server.key"));
+            assert_eq!(tls.client_ca_path, Some(PathBuf::from("certs/ca.pem")));
+        }
+        other => panic!("Unexpected handler: {:?}", other),
+    }
+}
+
+#[test]
+fn test_logging_tracing_config() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+
+    let manifest_content = r#"
+name = "traced-actor"
+component_path = "test.wasm"
+
+[interface]
+implements = "ntwk:simple-actor/actor"
+requires = []
+
+[logging.tracing]
+service_name = "traced"
+"#;
+
+    write!(temp_file, "{}", manifest_content).unwrap();
+
+    let config = ManifestConfig::from_file(temp_file.path()).unwrap();
+    let tracing = config.logging.tracing.expect("tracing should be configured");
+    assert_eq!(tracing.otlp_endpoint, "http://localhost:4318/v1/traces");
+    assert_eq!(tracing.service_name.as_deref(), Some("traced"));
+    assert_eq!(config.logging.level, "info");
+}
+
+#[test]
+fn test_logging_output_config() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+
+    let manifest_content = r#"
+name = "logged-actor"
+component_path = "test.wasm"
+
+[interface]
+implements = "ntwk:simple-actor/actor"
+requires = []
+
+[logging]
+output = "file"
+file_path = "logs/actor.log"
+format = "json"
+chain_events = true
+"#;
+
+    write!(temp_file, "{}", manifest_content).unwrap();
+
+    let config = ManifestConfig::from_file(temp_file.path()).unwrap();
+    assert!(matches!(config.logging.output, LogOutput::File));
+    assert_eq!(config.logging.file_path, Some(PathBuf::from("logs/actor.log")));
+    assert!(matches!(config.logging.format, LogFormat::Json));
+    assert!(config.logging.chain_events);
+    assert_eq!(config.logging.max_file_bytes, 10 * 1024 * 1024);
+    assert_eq!(config.logging.max_files, 5);
+}
+
+#[test]
+fn test_zero_mailbox_capacity_rejected() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+
+    let manifest_content = r#"
+name = "busy-actor"
+component_path = "test.wasm"
+
+[interface]
+implements = "ntwk:simple-actor/actor"
+requires = []
+
+[mailbox]
+capacity = 0
+"#;
+
+    write!(temp_file, "{}", manifest_content).unwrap();
+
+    assert!(ManifestConfig::from_file(temp_file.path()).is_err());
+}
@@ -0,0 +1,371 @@
+use anyhow::Result;
+use serde_json::json;
+use std::time::Duration;
+use theater::chain::ChainEvent;
+use theater::{
+    Actor, ActorInput, ActorMessage, ActorOutput, ActorProcess, ControlMessage, MessageMetadata,
+};
+use tokio::sync::{mpsc, oneshot};
+
+async fn send_control(
+    control_tx: &mpsc::Sender<ControlMessage>,
+    control: ControlMessage,
+) -> Result<()> {
+    control_tx
+        .send(control)
+        .await
+        .map_err(|_| anyhow::anyhow!("control channel closed"))
+}
+
+struct CounterActor;
+
+impl Actor for CounterActor {
+    fn init(&self) -> Result<serde_json::Value> {
+        Ok(json!({"count": 0}))
+    }
+
+    fn handle_input(
+        &self,
+        input: ActorInput,
+        state: &serde_json::Value,
+    ) -> Result<(ActorOutput, serde_json::Value)> {
+        let count = state["count"].as_i64().unwrap_or(0) + 1;
+        let output = match input {
+            ActorInput::Message(msg) => ActorOutput::Message(msg),
+            _ => ActorOutput::Message(json!({"error": "unsupported input"})),
+        };
+        Ok((output, json!({"count": count})))
+    }
+
+    fn verify_state(&self, state: &serde_json::Value) -> bool {
+        state.get("count").is_some()
+    }
+}
+
+#[tokio::test]
+async fn test_control_serviced_before_mailbox() -> Result<()> {
+    let (tx, rx) = mpsc::channel(32);
+    let (control_tx, control_rx) = mpsc::channel(8);
+    let mut process = ActorProcess::new(&"counter".to_string(), Box::new(CounterActor), rx)?
+        .with_control(control_rx);
+
+    // Queue regular traffic before any control messages
+    for i in 0..5 {
+        tx.send(ActorMessage::new(
+            ActorInput::Message(json!({"n": i})),
+            None,
+        ))
+        .await?;
+    }
+
+    let (health_tx, health_rx) = oneshot::channel();
+    let (snapshot_tx, snapshot_rx) = oneshot::channel();
+    let (stop_tx, stop_rx) = oneshot::channel();
+    send_control(
+        &control_tx,
+        ControlMessage::HealthCheck {
+            response_channel: health_tx,
+        },
+    )
+    .await?;
+    send_control(
+        &control_tx,
+        ControlMessage::Snapshot {
+            response_channel: snapshot_tx,
+        },
+    )
+    .await?;
+    send_control(
+        &control_tx,
+        ControlMessage::Stop {
+            response_channel: stop_tx,
+        },
+    )
+    .await?;
+
+    process.run().await?;
+
+    let health = health_rx.await?;
+    assert!(health.state_valid);
+    let snapshot = snapshot_rx.await?;
+    assert_eq!(snapshot.state, Some(json!({"count": 0})));
+    stop_rx.await?;
+
+    // The actor stopped before touching any queued messages
+    assert_eq!(process.get_chain().get_full_chain().len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_upgrade_rejects_invalid_state() -> Result<()> {
+    struct StrictActor;
+
+    impl Actor for StrictActor {
+        fn init(&self) -> Result<serde_json::Value> {
+            Ok(json!({"items": []}))
+        }
+
+        fn handle_input(
+            &self,
+            _input: ActorInput,
+            state: &serde_json::Value,
+        ) -> Result<(ActorOutput, serde_json::Value)> {
+            Ok((ActorOutput::Message(json!(null)), state.clone()))
+        }
+
+        fn verify_state(&self, state: &serde_json::Value) -> bool {
+            state.get("items").is_some()
+        }
+    }
+
+    let (_tx, rx) = mpsc::channel(32);
+    let (control_tx, control_rx) = mpsc::channel(8);
+    let mut process = ActorProcess::new(&"counter".to_string(), Box::new(CounterActor), rx)?
+        .with_control(control_rx);
+
+    let (upgrade_tx, upgrade_rx) = oneshot::channel();
+    let (stop_tx, _stop_rx) = oneshot::channel();
+    send_control(
+        &control_tx,
+        ControlMessage::Upgrade {
+            actor: Box::new(StrictActor),
+            component: "strict.wasm".to_string(),
+            response_channel: upgrade_tx,
+        },
+    )
+    .await?;
+    send_control(
+        &control_tx,
+        ControlMessage::Stop {
+            response_channel: stop_tx,
+        },
+    )
+    .await?;
+
+    process.run().await?;
+
+    assert!(upgrade_rx.await?.is_err());
+    assert_eq!(process.get_chain().get_full_chain().len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_shutdown_drains_mailbox() -> Result<()> {
+    let (tx, rx) = mpsc::channel(32);
+    let (control_tx, control_rx) = mpsc::channel(8);
+    let mut process = ActorProcess::new(&"counter".to_string(), Box::new(CounterActor), rx)?
+        .with_control(control_rx);
+
+    for i in 0..3 {
+        tx.send(ActorMessage::new(
+            ActorInput::Message(json!({"n": i})),
+            None,
+        ))
+        .await?;
+    }
+
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    send_control(
+        &control_tx,
+        ControlMessage::Shutdown {
+            drain_timeout: Duration::from_secs(1),
+            response_channel: shutdown_tx,
+        },
+    )
+    .await?;
+
+    process.run().await?;
+    shutdown_rx.await?;
+
+    // No new input is accepted once the mailbox is closed
+    assert!(tx
+        .send(ActorMessage::new(
+            ActorInput::Message(json!({"n": 3})),
+            None
+        ))
+        .await
+        .is_err());
+
+    let chain = process.get_chain();
+    assert_eq!(chain.get_current_state(), Some(json!({"count": 3})));
+
+    let full_chain = chain.get_full_chain();
+    match &full_chain[0].1.event {
+        ChainEvent::Shutdown {
+            drained, abandoned, ..
+        } => {
+            assert_eq!(*drained, 3);
+            assert_eq!(*abandoned, 0);
+        }
+        other => panic!("Expected shutdown event, got {:?}", other),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_failed_input_is_isolated() -> Result<()> {
+    struct FlakyActor;
+
+    impl Actor for FlakyActor {
+        fn init(&self) -> Result<serde_json::Value> {
+            Ok(json!({"count": 0}))
+        }
+
+        fn handle_input(
+            &self,
+            input: ActorInput,
+            state: &serde_json::Value,
+        ) -> Result<(ActorOutput, serde_json::Value)> {
+            match input {
+                ActorInput::HttpRequest { .. } => Err(anyhow::anyhow!("handler trapped")),
+                ActorInput::Message(msg) => {
+                    let count = state["count"].as_i64().unwrap_or(0) + 1;
+                    Ok((ActorOutput::Message(msg), json!({"count": count})))
+                }
+            }
+        }
+
+        fn verify_state(&self, state: &serde_json::Value) -> bool {
+            state.get("count").is_some()
+        }
+    }
+
+    let (tx, rx) = mpsc::channel(32);
+    let mut process = ActorProcess::new(&"flaky".to_string(), Box::new(FlakyActor), rx)?;
+
+    let (response_tx, response_rx) = oneshot::channel();
+    tx.send(ActorMessage::new(
+        ActorInput::HttpRequest {
+            method: "GET".to_string(),
+            uri: "/".to_string(),
+            path: "/".to_string(),
+            query: vec![],
+            params: vec![],
+            headers: vec![],
+            body: None,
+            body_stream: None,
+            peer_addr: None,
+            version: None,
+            route_tag: None,
+            principal: None,
+        },
+        Some(MessageMetadata::HttpRequest {
+            response_channel: response_tx,
+            correlation_id: "req-42".to_string(),
+        }),
+    ))
+    .await?;
+    tx.send(ActorMessage::new(
+        ActorInput::Message(json!({"n": 1})),
+        None,
+    ))
+    .await?;
+    drop(tx);
+
+    // The loop keeps going after the failure and exits once the mailbox closes
+    process.run().await?;
+
+    match response_rx.await? {
+        ActorOutput::HttpResponse { status, body, .. } => {
+            assert_eq!(status, 500);
+            let body: serde_json::Value = serde_json::from_slice(&body.unwrap())?;
+            assert_eq!(body["correlation_id"], "req-42");
+            assert_eq!(body["error"], "handler trapped");
+        }
+        other => panic!("Unexpected output: {:?}", other),
+    }
+
+    let chain = process.get_chain();
+    assert_eq!(chain.get_current_state(), Some(json!({"count": 1})));
+    assert!(chain.get_full_chain().iter().any(|(_, entry)| matches!(
+        &entry.event,
+        ChainEvent::Error { correlation_id: Some(id), .. } if id == "req-42"
+    )));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_abandoned_response_is_recorded() -> Result<()> {
+    let (tx, rx) = mpsc::channel(32);
+    let mut process = ActorProcess::new(&"counter".to_string(), Box::new(CounterActor), rx)?;
+
+    // The caller has already given up by the time the actor responds
+    let (response_tx, response_rx) = oneshot::channel();
+    drop(response_rx);
+    tx.send(ActorMessage::new(
+        ActorInput::Message(json!({"n": 1})),
+        Some(MessageMetadata::HttpRequest {
+            response_channel: response_tx,
+            correlation_id: "req-7".to_string(),
+        }),
+    ))
+    .await?;
+    drop(tx);
+
+    process.run().await?;
+
+    let full_chain = process.get_chain().get_full_chain();
+    match (&full_chain[0].1.event, &full_chain[1]) {
+        (
+            ChainEvent::ResponseAbandoned {
+                correlation_id,
+                output,
+                ..
+            },
+            (output_hash, _),
+        ) => {
+            assert_eq!(correlation_id, "req-7");
+            assert_eq!(output, output_hash);
+        }
+        other => panic!("Expected abandoned response, got {:?}", other),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stop_rejects_queued_requests() -> Result<()> {
+    let (tx, rx) = mpsc::channel(32);
+    let (control_tx, control_rx) = mpsc::channel(8);
+    let mut process = ActorProcess::new(&"counter".to_string(), Box::new(CounterActor), rx)?
+        .with_control(control_rx);
+
+    let (response_tx, response_rx) = oneshot::channel();
+    tx.send(ActorMessage::new(
+        ActorInput::Message(json!({"n": 1})),
+        Some(MessageMetadata::HttpRequest {
+            response_channel: response_tx,
+            correlation_id: "req-9".to_string(),
+        }),
+    ))
+    .await?;
+
+    let (stop_tx, stop_rx) = oneshot::channel();
+    send_control(
+        &control_tx,
+        ControlMessage::Stop {
+            response_channel: stop_tx,
+        },
+    )
+    .await?;
+
+    process.run().await?;
+    stop_rx.await?;
+
+    // The queued request is answered instead of being dropped with the mailbox
+    match response_rx.await? {
+        ActorOutput::HttpResponse { status, body, .. } => {
+            assert_eq!(status, 503);
+            let body: serde_json::Value = serde_json::from_slice(&body.unwrap())?;
+            assert_eq!(body["correlation_id"], "req-9");
+        }
+        other => panic!("Unexpected output: {:?}", other),
+    }
+    assert_eq!(process.get_chain().get_full_chain().len(), 1);
+
+    Ok(())
+}
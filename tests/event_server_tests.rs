@@ -0,0 +1,362 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use futures::{SinkExt, Stream, StreamExt};
+use hyper::Body;
+use serde_json::{json, Value};
+use theater::chain_emitter::CHAIN_EMITTER;
+use theater::config::BindAddress;
+use theater::event_server::{self, NEXT_CURSOR_HEADER};
+use theater::listener::Mount;
+use theater::logging::{ChainEvent, ChainEventType};
+use theater::registry::ACTOR_REGISTRY;
+use theater::{Actor, ActorInput, ActorMessage, ActorOutput, ActorProcess, MessageMetadata};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+fn emit(actor: &str, hash: &str, event_type: ChainEventType, timestamp: DateTime<Utc>) {
+    CHAIN_EMITTER.emit(ChainEvent {
+        hash: hash.to_string(),
+        timestamp,
+        actor_name: actor.to_string(),
+        event_type,
+        data: json!({}),
+        parent: None,
+    });
+}
+
+/// GET a history query, returning the status, next cursor and event hashes
+async fn history(query: &str) -> Result<(u16, Option<String>, Vec<String>)> {
+    let request = hyper::Request::get(format!("/events/history?{}", query)).body(Body::empty())?;
+    let response = event_server::service()(request).await;
+    let status = response.status().as_u16();
+    let cursor = response
+        .headers()
+        .get(NEXT_CURSOR_HEADER)
+        .map(|cursor| cursor.to_str().unwrap().to_string());
+    let body: Value = serde_json::from_slice(&hyper::body::to_bytes(response).await?)?;
+    let hashes = body
+        .as_array()
+        .map(|events| {
+            events
+                .iter()
+                .map(|event| event["hash"].as_str().unwrap().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok((status, cursor, hashes))
+}
+
+#[tokio::test]
+async fn test_history_filters_and_pages() -> Result<()> {
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+    let at = |minutes| start + Duration::minutes(minutes);
+    emit("history-a", "ha0", ChainEventType::Init, at(0));
+    emit("history-b", "hb0", ChainEventType::Init, at(0));
+    for i in 1..5 {
+        emit(
+            "history-a",
+            &format!("ha{}", i),
+            ChainEventType::StateTransition,
+            at(i),
+        );
+    }
+
+    let (_, _, hashes) = history("actor=history-a").await?;
+    assert_eq!(hashes, ["ha0", "ha1", "ha2", "ha3", "ha4"]);
+
+    let (_, _, hashes) = history("actor=history-a,history-b&event_type=Init").await?;
+    assert_eq!(hashes, ["ha0", "hb0"]);
+
+    let (_, _, hashes) = history(&format!(
+        "actor=history-a&since={}&until={}",
+        url::form_urlencoded::byte_serialize(at(1).to_rfc3339().as_bytes()).collect::<String>(),
+        url::form_urlencoded::byte_serialize(at(3).to_rfc3339().as_bytes()).collect::<String>(),
+    ))
+    .await?;
+    assert_eq!(hashes, ["ha1", "ha2"]);
+
+    let (_, _, hashes) = history("hash_prefix=hb").await?;
+    assert_eq!(hashes, ["hb0"]);
+
+    // Pages follow the cursor until no more events match
+    let (_, cursor, hashes) = history("actor=history-a&limit=2").await?;
+    assert_eq!(hashes, ["ha0", "ha1"]);
+    assert_eq!(cursor.as_deref(), Some("ha1"));
+    let (_, cursor, hashes) = history("actor=history-a&limit=2&cursor=ha1").await?;
+    assert_eq!(hashes, ["ha2", "ha3"]);
+    let (_, cursor, hashes) = history(&format!(
+        "actor=history-a&limit=2&cursor={}",
+        cursor.unwrap()
+    ))
+    .await?;
+    assert_eq!(hashes, ["ha4"]);
+    assert_eq!(cursor, None);
+
+    let (status, _, _) = history("event_type=Bogus").await?;
+    assert_eq!(status, 400);
+    let (status, _, _) = history("since=yesterday").await?;
+    assert_eq!(status, 400);
+    let (status, _, _) = history("cursor=aged-out").await?;
+    assert_eq!(status, 410);
+
+    Ok(())
+}
+
+/// The next text message from the server, as JSON
+async fn next<S>(ws: &mut S) -> Result<Value>
+where
+    S: Stream<Item = Result<Message, WsError>> + Unpin,
+{
+    loop {
+        if let Some(Message::Text(text)) = ws.next().await.transpose()? {
+            return Ok(serde_json::from_str(&text)?);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_websocket_subscription_filter() -> Result<()> {
+    let events = Mount::bind(&BindAddress::parse("127.0.0.1", 18106)?, None, "/")?;
+    events.serve(event_server::service());
+
+    let (mut ws, _) =
+        tokio_tungstenite::connect_async("ws://127.0.0.1:18106/events/ws?actor=ws-a").await?;
+    // Invalid messages are answered, which also shows the server is listening
+    ws.send(Message::text("not json")).await?;
+    assert_eq!(next(&mut ws).await?["type"], "error");
+
+    emit("ws-b", "wb0", ChainEventType::Init, Utc::now());
+    emit("ws-a", "wa0", ChainEventType::Init, Utc::now());
+    assert_eq!(next(&mut ws).await?["hash"], "wa0");
+
+    ws.send(Message::text(
+        json!({ "type": "subscribe", "actors": ["ws-b"], "event_types": ["StateTransition"] })
+            .to_string(),
+    ))
+    .await?;
+    assert_eq!(next(&mut ws).await?["type"], "subscribed");
+
+    emit("ws-a", "wa1", ChainEventType::StateTransition, Utc::now());
+    emit("ws-b", "wb1", ChainEventType::Init, Utc::now());
+    emit("ws-b", "wb2", ChainEventType::StateTransition, Utc::now());
+    assert_eq!(next(&mut ws).await?["hash"], "wb2");
+
+    Ok(())
+}
+
+struct CounterActor;
+
+impl Actor for CounterActor {
+    fn init(&self) -> Result<Value> {
+        Ok(json!({ "count": 0 }))
+    }
+
+    fn handle_input(&self, input: ActorInput, state: &Value) -> Result<(ActorOutput, Value)> {
+        let count = state["count"].as_i64().unwrap_or(0) + 1;
+        let ActorInput::Message(msg) = input else {
+            anyhow::bail!("unsupported input");
+        };
+        Ok((ActorOutput::Message(msg), json!({ "count": count })))
+    }
+
+    fn verify_state(&self, state: &Value) -> bool {
+        state.get("count").is_some()
+    }
+}
+
+/// GET a route from the event server, returning the status, next cursor and body
+async fn get(path: &str) -> Result<(u16, Option<String>, Value)> {
+    let request = hyper::Request::get(path).body(Body::empty())?;
+    let response = event_server::service()(request).await;
+    let status = response.status().as_u16();
+    let cursor = response
+        .headers()
+        .get(NEXT_CURSOR_HEADER)
+        .map(|cursor| cursor.to_str().unwrap().to_string());
+    let body = hyper::body::to_bytes(response).await?;
+    let body = if body.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&body)?
+    };
+    Ok((status, cursor, body))
+}
+
+#[tokio::test]
+async fn test_actor_chain_routes() -> Result<()> {
+    let (tx, rx) = mpsc::channel(32);
+    let (control_tx, control_rx) = mpsc::channel(8);
+    let mut process = ActorProcess::new(&"chain-api".to_string(), Box::new(CounterActor), rx)?
+        .with_control(control_rx);
+    ACTOR_REGISTRY.register_control("chain-api", control_tx);
+    for n in 0..3 {
+        tx.send(ActorMessage::new(
+            ActorInput::Message(json!({ "n": n })),
+            None,
+        ))
+        .await?;
+    }
+    tokio::spawn(async move { process.run().await });
+
+    // Wait for the actor to commit every message: one initial state, then an
+    // input, state change and output for each
+    let head = loop {
+        let (_, _, body) = get("/actors/chain-api/chain/head").await?;
+        if body["length"] == 10 {
+            break body["head"].as_str().unwrap().to_string();
+        }
+        tokio::task::yield_now().await;
+    };
+
+    let (_, _, body) = get("/actors/chain-api/state").await?;
+    assert_eq!(body, json!({ "head": head, "state": { "count": 3 } }));
+
+    // Pages run from the head back to the initial state
+    let (_, cursor, body) = get("/actors/chain-api/chain?limit=4").await?;
+    let entries = body["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0][0], head.as_str());
+    assert_eq!(entries[0][1]["parent"], entries[1][0]);
+    let cursor = cursor.unwrap();
+    assert_eq!(entries[3][0], cursor.as_str());
+
+    let (_, cursor, body) = get(&format!("/actors/chain-api/chain?cursor={}", cursor)).await?;
+    let entries = body["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 6);
+    assert_eq!(cursor, None);
+    assert_eq!(entries[5][1]["parent"], Value::Null);
+
+    // The first entry holds the initial state
+    let (status, _, body) = get(&format!(
+        "/actors/chain-api/chain/{}",
+        entries[5][0].as_str().unwrap()
+    ))
+    .await?;
+    assert_eq!(status, 200);
+    assert_eq!(
+        body["event"]["StateChange"]["new_state"],
+        json!({ "count": 0 })
+    );
+    // An output carries the state committed just before it
+    assert!(entries[2][1]["event"]["Output"].is_object());
+    let (_, _, body) = get(&format!(
+        "/actors/chain-api/state/{}",
+        entries[2][0].as_str().unwrap()
+    ))
+    .await?;
+    assert_eq!(body["state"], json!({ "count": 1 }));
+
+    let (status, _, _) = get("/actors/chain-api/chain/missing").await?;
+    assert_eq!(status, 404);
+    let (status, _, _) = get("/actors/chain-api/state/missing").await?;
+    assert_eq!(status, 404);
+    let (status, _, body) = get("/actors/nobody/state").await?;
+    assert_eq!(status, 404);
+    assert_eq!(body["error"], "Unknown actor 'nobody'");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_emitted_events_name_actor_and_type() -> Result<()> {
+    let (tx, rx) = mpsc::channel(32);
+    let mut process = ActorProcess::new(&"typed-events".to_string(), Box::new(CounterActor), rx)?;
+    tx.send(ActorMessage::new(
+        ActorInput::Message(json!({ "n": 1 })),
+        None,
+    ))
+    .await?;
+    tx.send(ActorMessage::new(
+        ActorInput::Message(json!({ "n": 2 })),
+        Some(MessageMetadata::ActorSource {
+            source_actor: "peer".to_string(),
+            source_chain_state: "abc".to_string(),
+        }),
+    ))
+    .await?;
+    drop(tx);
+    process.run().await?;
+
+    let events: Vec<_> = CHAIN_EMITTER
+        .get_history()
+        .into_iter()
+        .filter(|event| event.actor_name == "typed-events")
+        .collect();
+    let types: Vec<_> = events
+        .iter()
+        .map(|event| event.event_type.clone())
+        .collect();
+    assert_eq!(
+        types,
+        [
+            ChainEventType::Init,
+            ChainEventType::Input,
+            ChainEventType::StateTransition,
+            ChainEventType::Output,
+            ChainEventType::Message,
+            ChainEventType::StateTransition,
+            ChainEventType::Output,
+        ]
+    );
+
+    // Each event carries the timestamp recorded in its chain entry
+    let mut chain = process.get_chain().get_full_chain();
+    chain.reverse();
+    for (event, (hash, entry)) in events.iter().zip(&chain) {
+        assert_eq!(&event.hash, hash);
+        assert_eq!(event.timestamp, entry.event.timestamp());
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_websocket_resume() -> Result<()> {
+    let events = Mount::bind(&BindAddress::parse("127.0.0.1", 18107)?, None, "/")?;
+    events.serve(event_server::service());
+    for i in 0..4 {
+        emit(
+            "resume-a",
+            &format!("ra{}", i),
+            ChainEventType::Input,
+            Utc::now(),
+        );
+        emit(
+            "resume-b",
+            &format!("rb{}", i),
+            ChainEventType::Input,
+            Utc::now(),
+        );
+    }
+
+    // Connecting with a cursor replays what followed it, then streams live events
+    let (mut ws, _) = tokio_tungstenite::connect_async(
+        "ws://127.0.0.1:18107/events/ws?actor=resume-a&cursor=ra1",
+    )
+    .await?;
+    assert_eq!(next(&mut ws).await?["hash"], "ra2");
+    assert_eq!(next(&mut ws).await?["hash"], "ra3");
+    let resumed = next(&mut ws).await?;
+    assert_eq!(resumed["type"], "resumed");
+    assert_eq!(resumed["replayed"], 2);
+    emit("resume-a", "ra4", ChainEventType::Input, Utc::now());
+    assert_eq!(next(&mut ws).await?["hash"], "ra4");
+
+    // Resuming from an event that is no longer stored replays everything kept
+    ws.send(Message::text(
+        json!({ "type": "resume", "from": "aged-out", "actors": ["resume-b"] }).to_string(),
+    ))
+    .await?;
+    let gap = next(&mut ws).await?;
+    assert_eq!(gap["type"], "gap");
+    assert_eq!(gap["from"], "aged-out");
+    for i in 0..4 {
+        assert_eq!(next(&mut ws).await?["hash"], format!("rb{}", i));
+    }
+    assert_eq!(next(&mut ws).await?["replayed"], 4);
+    emit("resume-a", "ra5", ChainEventType::Input, Utc::now());
+    emit("resume-b", "rb4", ChainEventType::Input, Utc::now());
+    assert_eq!(next(&mut ws).await?["hash"], "rb4");
+
+    Ok(())
+}
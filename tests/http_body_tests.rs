@@ -0,0 +1,160 @@
+use anyhow::Result;
+use base64::Engine as _;
+use serde_json::json;
+use std::io::Write;
+use tempfile::{Builder, NamedTempFile};
+use theater::config::ManifestConfig;
+use theater::{Actor, ActorInput, ActorOutput, Store, WasmActor};
+
+// 1x1 transparent PNG
+const PNG_BASE64: &str =
+    "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNkYAAAAAYAAjCB0C8AAAAASUVORK5CYII=";
+
+/// Component for the JSON `http-actor` interface whose `handle-http` always
+/// returns `response` as its encoded output
+fn json_http_actor(response: &serde_json::Value) -> String {
+    let output = serde_json::to_string(response).unwrap();
+    format!(
+        r#"
+(component
+    (core module $m
+        (memory (export "memory") 1)
+        (global $bump (mut i32) (i32.const 8192))
+        (data (i32.const 2048) "{data}")
+
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+            (local $ret i32)
+            (local.set $ret
+                (i32.and (i32.add (global.get $bump) (i32.const 7)) (i32.const -8)))
+            (global.set $bump (i32.add (local.get $ret) (local.get 3)))
+            (local.get $ret))
+
+        (func (export "handle-http") (param i32 i32 i32 i32) (result i32)
+            (i32.store (i32.const 1024) (i32.const 2048))
+            (i32.store (i32.const 1028) (i32.const {len}))
+            (i32.const 1024))
+
+        (func (export "init") (result i32) unreachable)
+        (func (export "handle") (param i32 i32 i32 i32) (result i32) unreachable)
+        (func (export "state-contract") (param i32 i32) (result i32) unreachable)
+        (func (export "message-contract") (param i32 i32 i32 i32) (result i32) unreachable)
+        (func (export "http-contract") (param i32 i32 i32 i32) (result i32) unreachable)
+    )
+    (core instance $i (instantiate $m))
+    (alias core export $i "memory" (core memory $mem))
+    (alias core export $i "realloc" (core func $realloc))
+
+    (type $bytes (list u8))
+    (func $init (result $bytes)
+        (canon lift (core func $i "init") (memory $mem)))
+    (func $handle (param "msg" $bytes) (param "state" $bytes) (result $bytes)
+        (canon lift (core func $i "handle") (memory $mem) (realloc $realloc)))
+    (func $state-contract (param "state" $bytes) (result bool)
+        (canon lift (core func $i "state-contract") (memory $mem) (realloc $realloc)))
+    (func $message-contract (param "msg" $bytes) (param "state" $bytes) (result bool)
+        (canon lift (core func $i "message-contract") (memory $mem) (realloc $realloc)))
+    (func $http-contract (param "req" $bytes) (param "state" $bytes) (result bool)
+        (canon lift (core func $i "http-contract") (memory $mem) (realloc $realloc)))
+    (func $handle-http (param "req" $bytes) (param "state" $bytes) (result $bytes)
+        (canon lift (core func $i "handle-http") (memory $mem) (realloc $realloc)))
+
+    (instance $actor
+        (export "init" (func $init))
+        (export "handle" (func $handle))
+        (export "state-contract" (func $state-contract))
+        (export "message-contract" (func $message-contract))
+        (export "http-contract" (func $http-contract))
+        (export "handle-http" (func $handle-http)))
+    (export "ntwk:simple-http-actor/http-actor" (instance $actor))
+)
+"#,
+        data = output.replace('\\', "\\\\").replace('"', "\\\""),
+        len = output.len(),
+    )
+}
+
+fn respond_with(body: serde_json::Value) -> Result<Option<Vec<u8>>> {
+    let mut component = Builder::new().suffix(".wat").tempfile()?;
+    write!(
+        component,
+        "{}",
+        json_http_actor(&json!({
+            "response": {
+                "status": 200,
+                "headers": {"Content-Type": "image/png"},
+                "body": body,
+            },
+            "state": {"served": true},
+        }))
+    )?;
+
+    let mut manifest = NamedTempFile::new()?;
+    write!(
+        manifest,
+        r#"
+name = "image-actor"
+component_path = "{}"
+
+[interface]
+implements = "ntwk:simple-http-actor/http-actor"
+requires = []
+"#,
+        component.path().display()
+    )?;
+
+    let config = ManifestConfig::from_file(manifest.path())?;
+    let actor = WasmActor::new(&config, Store::new())?;
+
+    let (output, new_state) = actor.handle_input(
+        ActorInput::HttpRequest {
+            method: "GET".to_string(),
+            uri: "http://127.0.0.1:8081/pixel.png".to_string(),
+            path: "/pixel.png".to_string(),
+            query: vec![],
+            params: vec![],
+            headers: vec![],
+            body: None,
+            body_stream: None,
+            peer_addr: None,
+            version: None,
+            route_tag: None,
+            principal: None,
+        },
+        &json!({}),
+    )?;
+    assert_eq!(new_state, json!({"served": true}));
+
+    match output {
+        ActorOutput::HttpResponse { body, .. } => Ok(body),
+        other => panic!("Unexpected output: {:?}", other),
+    }
+}
+
+#[test]
+fn test_base64_response_body() -> Result<()> {
+    let png = base64::engine::general_purpose::STANDARD.decode(PNG_BASE64)?;
+    let body = respond_with(json!({ "base64": PNG_BASE64 }))?;
+    assert_eq!(body, Some(png));
+    Ok(())
+}
+
+#[test]
+fn test_byte_array_response_body() -> Result<()> {
+    let png = base64::engine::general_purpose::STANDARD.decode(PNG_BASE64)?;
+    let body = respond_with(json!(png))?;
+    assert_eq!(body, Some(png));
+    Ok(())
+}
+
+#[test]
+fn test_text_response_body() -> Result<()> {
+    let body = respond_with(json!("<h1>Hello</h1>"))?;
+    assert_eq!(body, Some(b"<h1>Hello</h1>".to_vec()));
+    Ok(())
+}
+
+#[test]
+fn test_invalid_response_body() {
+    assert!(respond_with(json!([0, 256])).is_err());
+    assert!(respond_with(json!({ "base64": "not base64!" })).is_err());
+}
@@ -0,0 +1,243 @@
+mod common;
+
+use anyhow::Result;
+use base64::Engine as _;
+use serde_json::json;
+use theater::config::{HttpServerHandlerConfig, Route, RouteMatch, RouteTimeout};
+use theater::http_server::{HttpServerHandler, CORRELATION_ID_HEADER};
+use theater::{ActorInput, ActorMessage, ActorOutput};
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn test_request_timeout_returns_504() -> Result<()> {
+    let handler = HttpServerHandler::from_config(HttpServerHandlerConfig {
+        port: 18091,
+        timeout_ms: 100,
+        ..Default::default()
+    });
+
+    // Nothing ever answers requests queued in this mailbox
+    let (tx, mut rx) = mpsc::channel(32);
+    common::start(handler, tx).await?;
+    let _held = tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Some(msg) = rx.recv().await {
+            held.push(msg);
+        }
+    });
+
+    let response = reqwest::get("http://127.0.0.1:18091/slow").await?;
+    assert_eq!(response.status().as_u16(), 504);
+
+    let correlation_id = response
+        .headers()
+        .get(CORRELATION_ID_HEADER)
+        .expect("missing correlation id")
+        .to_str()?
+        .to_string();
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["correlation_id"], correlation_id);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_full_mailbox_times_out() -> Result<()> {
+    let handler = HttpServerHandler::from_config(HttpServerHandlerConfig {
+        port: 18109,
+        timeout_ms: 100,
+        ..Default::default()
+    });
+
+    // The mailbox is already full and nothing ever takes from it, so the
+    // request waits for room until it times out
+    let (tx, _rx) = mpsc::channel(1);
+    tx.send(ActorMessage::new(ActorInput::Message(json!(null)), None))
+        .await?;
+    common::start(handler, tx).await?;
+
+    let response = reqwest::get("http://127.0.0.1:18109/stuck").await?;
+    assert_eq!(response.status().as_u16(), 504);
+    let correlation_id = response
+        .headers()
+        .get(CORRELATION_ID_HEADER)
+        .expect("missing correlation id")
+        .to_str()?
+        .to_string();
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["correlation_id"], correlation_id);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_fields_forwarded() -> Result<()> {
+    let handler = HttpServerHandler::new(18092);
+
+    common::start_echo(handler).await?;
+
+    let response = reqwest::get("http://127.0.0.1:18092/search?q=actor%20model&page=2").await?;
+    let input: ActorInput = response.json().await?;
+    match input {
+        ActorInput::HttpRequest {
+            uri,
+            path,
+            query,
+            peer_addr,
+            version,
+            ..
+        } => {
+            assert_eq!(uri, "http://127.0.0.1:18092/search?q=actor%20model&page=2");
+            assert_eq!(path, "/search");
+            assert_eq!(
+                query,
+                vec![
+                    ("q".to_string(), "actor model".to_string()),
+                    ("page".to_string(), "2".to_string()),
+                ]
+            );
+            assert!(peer_addr.unwrap().starts_with("127.0.0.1:"));
+            assert_eq!(version.as_deref(), Some("HTTP/1.1"));
+        }
+        other => panic!("Unexpected input: {:?}", other),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_binary_body_round_trip() -> Result<()> {
+    // 1x1 transparent PNG
+    let png = base64::engine::general_purpose::STANDARD.decode(
+        "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNkYAAAAAYAAjCB0C8AAAAASUVORK5CYII=",
+    )?;
+    let handler = HttpServerHandler::new(18093);
+
+    let served = png.clone();
+    common::start_actor(handler, move |_| ActorOutput::HttpResponse {
+        status: 200,
+        headers: vec![("Content-Type".to_string(), "image/png".to_string())],
+        body: Some(served.clone()),
+    })
+    .await?;
+
+    let response = reqwest::get("http://127.0.0.1:18093/pixel.png").await?;
+    assert_eq!(response.headers()["content-type"], "image/png");
+    assert_eq!(response.bytes().await?.to_vec(), png);
+
+    Ok(())
+}
+
+#[test]
+fn test_route_timeouts() {
+    let config = HttpServerHandlerConfig {
+        port: 8081,
+        timeout_ms: 1000,
+        route_timeouts: vec![
+            RouteTimeout {
+                path: "/reports".to_string(),
+                timeout_ms: 60_000,
+            },
+            RouteTimeout {
+                path: "/reports/quick".to_string(),
+                timeout_ms: 500,
+            },
+        ],
+        ..Default::default()
+    };
+
+    assert_eq!(config.timeout_for("/"), 1000);
+    assert_eq!(config.timeout_for("/reports/annual"), 60_000);
+    assert_eq!(config.timeout_for("/reports/quick/today"), 500);
+    // Prefixes only match whole path segments
+    assert_eq!(config.timeout_for("/reportsv2"), 1000);
+}
+
+#[test]
+fn test_route_matching() {
+    let route = |method: Option<&str>, path: &str| Route {
+        method: method.map(str::to_string),
+        path: path.to_string(),
+        tag: None,
+    };
+    let config = HttpServerHandlerConfig {
+        port: 8081,
+        timeout_ms: 1000,
+        routes: vec![
+            route(Some("GET"), "/users/:id"),
+            route(Some("PUT"), "/users/:id"),
+            route(None, "/static/*file"),
+        ],
+        ..Default::default()
+    };
+
+    match config.route_for("get", "/users/42") {
+        RouteMatch::Matched { route, params } => {
+            assert_eq!(route.method.as_deref(), Some("GET"));
+            assert_eq!(params, vec![("id".to_string(), "42".to_string())]);
+        }
+        other => panic!("Unexpected match: {:?}", other),
+    }
+    match config.route_for("POST", "/static/css/site.css") {
+        RouteMatch::Matched { params, .. } => {
+            assert_eq!(
+                params,
+                vec![("file".to_string(), "css/site.css".to_string())]
+            );
+        }
+        other => panic!("Unexpected match: {:?}", other),
+    }
+    match config.route_for("DELETE", "/users/42") {
+        RouteMatch::MethodNotAllowed(allowed) => assert_eq!(allowed, vec!["GET", "PUT"]),
+        other => panic!("Unexpected match: {:?}", other),
+    }
+    assert!(matches!(
+        config.route_for("GET", "/users"),
+        RouteMatch::NotFound
+    ));
+    assert!(matches!(
+        config.route_for("GET", "/users/42/posts"),
+        RouteMatch::NotFound
+    ));
+}
+
+#[tokio::test]
+async fn test_routes_dispatch_and_reject() -> Result<()> {
+    let handler = HttpServerHandler::from_config(HttpServerHandlerConfig {
+        port: 18094,
+        timeout_ms: 1000,
+        routes: vec![Route {
+            method: Some("GET".to_string()),
+            path: "/users/:id".to_string(),
+            tag: Some("get-user".to_string()),
+        }],
+        ..Default::default()
+    });
+
+    common::start_echo(handler).await?;
+
+    let client = reqwest::Client::new();
+    let response = client.get("http://127.0.0.1:18094/users/42").send().await?;
+    match response.json::<ActorInput>().await? {
+        ActorInput::HttpRequest {
+            params, route_tag, ..
+        } => {
+            assert_eq!(params, vec![("id".to_string(), "42".to_string())]);
+            assert_eq!(route_tag.as_deref(), Some("get-user"));
+        }
+        other => panic!("Unexpected input: {:?}", other),
+    }
+
+    let response = client.get("http://127.0.0.1:18094/orders/1").send().await?;
+    assert_eq!(response.status().as_u16(), 404);
+    assert!(response.headers().contains_key(CORRELATION_ID_HEADER));
+
+    let response = client
+        .delete("http://127.0.0.1:18094/users/42")
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 405);
+    assert_eq!(response.headers()["allow"], "GET");
+
+    Ok(())
+}
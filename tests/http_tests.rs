@@ -59,8 +59,16 @@ async fn test_http_server_request_handling() -> Result<()> {
         content: ActorInput::HttpRequest {
             method: "GET".to_string(),
             uri: "/test".to_string(),
+            path: "/test".to_string(),
+            query: vec![],
+            params: vec![],
             headers: vec![],
             body: None,
+            body_stream: None,
+            peer_addr: None,
+            version: None,
+            route_tag: None,
+            principal: None,
         },
         response_channel: Some(response_tx),
     };
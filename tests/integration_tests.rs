@@ -100,8 +100,16 @@ async fn test_wasm_actor_http_handling() -> Result<()> {
         ActorInput::HttpRequest {
             method: "GET".to_string(),
             uri: "/".to_string(),
+            path: "/".to_string(),
+            query: vec![],
+            params: vec![],
             headers: vec![],
             body: None,
+            body_stream: None,
+            peer_addr: None,
+            version: None,
+            route_tag: None,
+            principal: None,
         },
         &initial_state,
     )?;
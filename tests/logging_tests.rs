@@ -0,0 +1,102 @@
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::io::Write;
+use tempfile::TempDir;
+use theater::chain::{ChainEvent, HashChain};
+use theater::chain_emitter::CHAIN_EMITTER;
+use theater::config::{LogFormat, LogOutput, LoggingConfig};
+use theater::logging::{self, RollingFile, CHAIN_TARGET};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::EnvFilter;
+
+fn state(n: i64) -> ChainEvent {
+    ChainEvent::StateChange {
+        old_state: json!(null),
+        new_state: json!({ "n": n }),
+        timestamp: Utc::now(),
+    }
+}
+
+#[test]
+fn test_json_lines_written_to_file() -> Result<()> {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("actor.log");
+    let config = LoggingConfig {
+        output: LogOutput::File,
+        file_path: Some(path.clone()),
+        format: LogFormat::Json,
+        ..Default::default()
+    };
+    let subscriber = tracing_subscriber::registry()
+        .with(EnvFilter::new("info"))
+        .with(logging::fmt_layer(&config)?);
+
+    let hash = tracing::subscriber::with_default(subscriber, || {
+        let _span = tracing::info_span!("actor.handle").entered();
+        tracing::info!(count = 3, "[TEST] Handled");
+
+        // Chain commits are only logged once enabled
+        let mut chain = HashChain::new("logged");
+        chain.add_event(state(1));
+        CHAIN_EMITTER.set_log_events(true);
+        let hash = chain.add_event(state(2));
+        CHAIN_EMITTER.set_log_events(false);
+        chain.add_event(state(3));
+        hash
+    });
+
+    let contents = std::fs::read_to_string(&path)?;
+    let lines: Vec<Value> = contents
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(lines.len(), 2);
+
+    assert_eq!(lines[0]["level"], "INFO");
+    assert_eq!(lines[0]["message"], "[TEST] Handled");
+    assert_eq!(lines[0]["count"], 3);
+    assert_eq!(lines[0]["spans"], json!(["actor.handle"]));
+
+    assert_eq!(lines[1]["target"], CHAIN_TARGET);
+    assert_eq!(lines[1]["hash"], hash);
+    assert_eq!(lines[1]["actor"], "logged");
+    assert_eq!(lines[1]["event_type"], "StateTransition");
+
+    Ok(())
+}
+
+#[test]
+fn test_file_output_requires_path() {
+    let config = LoggingConfig {
+        output: LogOutput::File,
+        ..Default::default()
+    };
+    let layer = logging::fmt_layer::<tracing_subscriber::Registry>(&config);
+    assert!(layer.is_err());
+}
+
+#[test]
+fn test_log_file_rotation() -> Result<()> {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("actor.log");
+    let file = RollingFile::open(&path, 10, 2)?;
+
+    for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+        (&file).write_all(line.as_bytes())?;
+    }
+
+    // Each line overflows the limit, so only the newest three are kept
+    assert_eq!(std::fs::read_to_string(&path)?, "fourth\n");
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("actor.log.1"))?,
+        "third\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("actor.log.2"))?,
+        "second\n"
+    );
+    assert!(!dir.path().join("actor.log.3").exists());
+
+    Ok(())
+}
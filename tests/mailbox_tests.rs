@@ -0,0 +1,131 @@
+use anyhow::Result;
+use serde_json::json;
+use theater::config::{MailboxConfig, OverflowPolicy};
+use theater::mailbox::{self, MailboxError};
+use theater::metrics::METRICS;
+use theater::{ActorInput, ActorMessage, ActorOutput, MessageMetadata};
+use tokio::sync::oneshot;
+
+fn message(n: i64) -> ActorMessage {
+    ActorMessage::new(ActorInput::Message(json!({ "n": n })), None)
+}
+
+#[tokio::test]
+async fn test_reject_when_full() -> Result<()> {
+    let config = MailboxConfig {
+        capacity: 2,
+        overflow: OverflowPolicy::Reject,
+        reject_status: 429,
+        ..Default::default()
+    };
+    let (mailbox, mut rx) = mailbox::channel("reject-mailbox", &config)?;
+
+    mailbox.send(message(1)).await?;
+    mailbox.send(message(2)).await?;
+    assert_eq!(mailbox.depth(), 2);
+    let labels = [("actor", "reject-mailbox")];
+    assert_eq!(METRICS.mailbox_depth.get(&labels), 2.0);
+
+    assert!(matches!(
+        mailbox.send(message(3)).await,
+        Err(MailboxError::Full)
+    ));
+    assert_eq!(mailbox.reject_status(), 429);
+
+    // Draining frees capacity again
+    rx.recv().await.expect("message missing");
+    assert_eq!(METRICS.mailbox_depth.get(&labels), 1.0);
+    mailbox.send(message(3)).await?;
+
+    Ok(())
+}
+
+#[test]
+fn test_zero_capacity_rejected() {
+    let config = MailboxConfig {
+        capacity: 0,
+        ..Default::default()
+    };
+    assert!(mailbox::channel("empty-mailbox", &config).is_err());
+}
+
+#[tokio::test]
+async fn test_drop_oldest_when_full() -> Result<()> {
+    let config = MailboxConfig {
+        capacity: 2,
+        overflow: OverflowPolicy::DropOldest,
+        reject_status: 503,
+        ..Default::default()
+    };
+    let (mailbox, mut rx) = mailbox::channel("drop-oldest-mailbox", &config)?;
+
+    let (response_tx, response_rx) = oneshot::channel();
+    mailbox
+        .send(ActorMessage::new(
+            ActorInput::Message(json!({ "n": 1 })),
+            Some(MessageMetadata::HttpRequest {
+                response_channel: response_tx,
+                correlation_id: "req-1".to_string(),
+            }),
+        ))
+        .await?;
+    mailbox.send(message(2)).await?;
+    mailbox.send(message(3)).await?;
+
+    // The evicted request is answered instead of being left hanging
+    match response_rx.await? {
+        ActorOutput::HttpResponse { status, body, .. } => {
+            assert_eq!(status, 503);
+            let body: serde_json::Value = serde_json::from_slice(&body.unwrap())?;
+            assert_eq!(body["correlation_id"], "req-1");
+        }
+        other => panic!("Unexpected output: {:?}", other),
+    }
+
+    for expected in [2, 3] {
+        match rx.recv().await.expect("message missing").content {
+            ActorInput::Message(msg) => assert_eq!(msg["n"], expected),
+            other => panic!("Unexpected input: {:?}", other),
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_drop_oldest_while_receiver_waits() -> Result<()> {
+    let config = MailboxConfig {
+        capacity: 1,
+        overflow: OverflowPolicy::DropOldest,
+        ..Default::default()
+    };
+    let (mailbox, mut rx) = mailbox::channel("waiting-mailbox", &config)?;
+
+    // Senders racing a receiver that keeps taking the lock all get through
+    let receiver = tokio::spawn(async move {
+        let mut received = 0;
+        while rx.recv().await.is_some() {
+            received += 1;
+        }
+        received
+    });
+    let senders: Vec<_> = (0..8)
+        .map(|n| {
+            let mailbox = mailbox.clone();
+            tokio::spawn(async move {
+                for i in 0..50 {
+                    mailbox.send(message(n * 50 + i)).await.unwrap();
+                }
+            })
+        })
+        .collect();
+    for sender in senders {
+        tokio::time::timeout(std::time::Duration::from_secs(5), sender).await??;
+    }
+    drop(mailbox);
+
+    let received = receiver.await?;
+    assert!(received > 0 && received <= 400);
+
+    Ok(())
+}
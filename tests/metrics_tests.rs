@@ -0,0 +1,119 @@
+use anyhow::Result;
+use hyper::{Body, StatusCode};
+use serde_json::{json, Value};
+use theater::event_server;
+use theater::listener;
+use theater::metrics::{self, METRICS};
+use theater::{Actor, ActorInput, ActorMessage, ActorOutput, ActorProcess};
+use tokio::sync::mpsc;
+
+/// Counts messages, failing on any that is not a number
+struct StrictCounter;
+
+impl Actor for StrictCounter {
+    fn init(&self) -> Result<Value> {
+        Ok(json!(0))
+    }
+
+    fn handle_input(&self, input: ActorInput, state: &Value) -> Result<(ActorOutput, Value)> {
+        match input {
+            ActorInput::Message(msg) if msg.is_number() => Ok((
+                ActorOutput::Message(msg),
+                json!(state.as_i64().unwrap_or(0) + 1),
+            )),
+            _ => anyhow::bail!("not a number"),
+        }
+    }
+
+    fn verify_state(&self, state: &Value) -> bool {
+        state.is_number()
+    }
+}
+
+async fn scrape() -> Result<String> {
+    let request = hyper::Request::get("/metrics").body(Body::empty())?;
+    let response = event_server::service()(request).await;
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/plain; version=0.0.4"
+    );
+    let body = hyper::body::to_bytes(response).await?;
+    Ok(String::from_utf8(body.to_vec())?)
+}
+
+#[tokio::test]
+async fn test_actor_metrics_are_scraped() -> Result<()> {
+    let (tx, rx) = mpsc::channel(32);
+    let mut process = ActorProcess::new(&"metered".to_string(), Box::new(StrictCounter), rx)?;
+    for msg in [json!(1), json!(2), json!("three")] {
+        tx.send(ActorMessage::new(ActorInput::Message(msg), None))
+            .await?;
+    }
+    drop(tx);
+    process.run().await?;
+
+    let text = scrape().await?;
+    assert!(text.contains("# TYPE theater_messages_handled_total counter"));
+    assert!(text.contains(r#"theater_messages_handled_total{actor="metered",outcome="ok"} 2"#));
+    assert!(text.contains(r#"theater_messages_handled_total{actor="metered",outcome="error"} 1"#));
+    assert!(text.contains("# TYPE theater_handler_duration_seconds histogram"));
+    assert!(
+        text.contains(r#"theater_handler_duration_seconds_bucket{actor="metered",le="+Inf"} 3"#)
+    );
+    assert!(text.contains(r#"theater_handler_duration_seconds_count{actor="metered"} 3"#));
+    // The initial state, then input, state and output for each good message
+    // and input and error for the bad one
+    assert!(text.contains(r#"theater_chain_length{actor="metered"} 9"#));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_responses_counted_by_status() -> Result<()> {
+    let service = listener::service(|req: listener::Request| async move {
+        let mut response = listener::Response::default();
+        if req.uri().path() != "/" {
+            *response.status_mut() = StatusCode::NOT_FOUND;
+        }
+        response
+    });
+    let service = metrics::count_responses(service, "test", "127.0.0.1:9999/".to_string());
+    for path in ["/", "/", "/missing"] {
+        service(hyper::Request::get(path).body(Body::empty())?).await;
+    }
+
+    let labels = |status| {
+        [
+            ("handler", "test"),
+            ("address", "127.0.0.1:9999/"),
+            ("status", status),
+        ]
+    };
+    assert_eq!(METRICS.http_responses.get(&labels("200")), 2);
+    assert_eq!(METRICS.http_responses.get(&labels("404")), 1);
+    assert!(scrape().await?.contains(
+        r#"theater_http_responses_total{handler="test",address="127.0.0.1:9999/",status="404"} 1"#
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stopped_actor_series_removed() -> Result<()> {
+    let (tx, rx) = mpsc::channel(32);
+    let mut process = ActorProcess::new(&"stopping".to_string(), Box::new(StrictCounter), rx)?;
+    tx.send(ActorMessage::new(ActorInput::Message(json!(1)), None))
+        .await?;
+    drop(tx);
+    process.run().await?;
+    assert!(scrape()
+        .await?
+        .contains(r#"theater_chain_length{actor="stopping"} 4"#));
+
+    METRICS.remove_actor("stopping");
+    let text = scrape().await?;
+    assert!(!text.contains(r#"actor="stopping""#));
+    assert!(text.contains("# TYPE theater_chain_length gauge"));
+
+    Ok(())
+}
@@ -0,0 +1,239 @@
+mod common;
+
+use anyhow::Result;
+use std::io::Read;
+use theater::config::{HttpServerHandlerConfig, MiddlewareConfig};
+use theater::http_server::HttpServerHandler;
+use theater::ActorInput;
+
+async fn start_echo_server(port: u16, middleware: Vec<MiddlewareConfig>) -> Result<()> {
+    let handler = HttpServerHandler::from_config(HttpServerHandlerConfig {
+        port,
+        timeout_ms: 1000,
+        middleware,
+        ..Default::default()
+    });
+
+    common::start_echo(handler).await?;
+    Ok(())
+}
+
+fn header<'a>(input: &'a ActorInput, name: &str) -> Option<&'a str> {
+    match input {
+        ActorInput::HttpRequest { headers, .. } => headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str()),
+        _ => None,
+    }
+}
+
+#[tokio::test]
+async fn test_request_id_and_compression() -> Result<()> {
+    start_echo_server(
+        18095,
+        vec![
+            MiddlewareConfig::RequestId {
+                header: "X-Request-Id".to_string(),
+            },
+            MiddlewareConfig::Logging,
+            MiddlewareConfig::Compression { min_bytes: 10 },
+        ],
+    )
+    .await?;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get("http://127.0.0.1:18095/items")
+        .header("Accept-Encoding", "gzip")
+        .send()
+        .await?;
+    assert_eq!(response.headers()["content-encoding"], "gzip");
+    assert_eq!(response.headers()["content-type"], "application/json");
+    let request_id = response.headers()["x-request-id"].to_str()?.to_string();
+
+    let mut body = Vec::new();
+    flate2::read::GzDecoder::new(&response.bytes().await?[..]).read_to_end(&mut body)?;
+    let input: ActorInput = serde_json::from_slice(&body)?;
+    assert_eq!(header(&input, "X-Request-Id"), Some(request_id.as_str()));
+
+    // Brotli is preferred, and a client supplied id is kept
+    let response = client
+        .get("http://127.0.0.1:18095/items")
+        .header("Accept-Encoding", "gzip, br")
+        .header("X-Request-Id", "client-id")
+        .send()
+        .await?;
+    assert_eq!(response.headers()["content-encoding"], "br");
+    assert_eq!(response.headers()["x-request-id"], "client-id");
+
+    let mut body = Vec::new();
+    brotli::Decompressor::new(&response.bytes().await?[..], 4096).read_to_end(&mut body)?;
+    let input: ActorInput = serde_json::from_slice(&body)?;
+    assert_eq!(header(&input, "X-Request-Id"), Some("client-id"));
+
+    // Without Accept-Encoding the body is sent as is
+    let response = client.get("http://127.0.0.1:18095/items").send().await?;
+    assert!(response.headers().get("content-encoding").is_none());
+    serde_json::from_slice::<ActorInput>(&response.bytes().await?)?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_body_limit_and_cors() -> Result<()> {
+    start_echo_server(
+        18096,
+        vec![
+            MiddlewareConfig::Cors {
+                allow_origins: vec!["https://app.example.com".to_string()],
+                allow_methods: "GET, POST".to_string(),
+                allow_headers: "*".to_string(),
+                max_age_secs: 600,
+            },
+            MiddlewareConfig::BodyLimit { max_bytes: 16 },
+        ],
+    )
+    .await?;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post("http://127.0.0.1:18096/upload")
+        .body(vec![0u8; 100])
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 413);
+
+    let response = client
+        .post("http://127.0.0.1:18096/upload")
+        .body(vec![7u8; 16])
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 200);
+    match response.json::<ActorInput>().await? {
+        ActorInput::HttpRequest { body, .. } => assert_eq!(body, Some(vec![7u8; 16])),
+        other => panic!("Unexpected input: {:?}", other),
+    }
+
+    let response = client
+        .request(reqwest::Method::OPTIONS, "http://127.0.0.1:18096/upload")
+        .header("Origin", "https://app.example.com")
+        .header("Access-Control-Request-Method", "POST")
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+    assert_eq!(
+        response.headers()["access-control-allow-methods"],
+        "GET, POST"
+    );
+
+    let response = client
+        .get("http://127.0.0.1:18096/upload")
+        .header("Origin", "https://app.example.com")
+        .send()
+        .await?;
+    assert_eq!(
+        response.headers()["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_partial_responses_not_compressed() -> Result<()> {
+    let service = theater::listener::service(|_req: theater::listener::Request| async {
+        let mut response = theater::listener::Response::new("x".repeat(100).into());
+        *response.status_mut() = hyper::StatusCode::PARTIAL_CONTENT;
+        response
+            .headers_mut()
+            .insert("Content-Range", "bytes 0-99/1000".parse().unwrap());
+        response
+    });
+    let service =
+        theater::middleware::apply(service, &MiddlewareConfig::Compression { min_bytes: 10 })?;
+
+    let request = hyper::Request::get("/file")
+        .header("Accept-Encoding", "gzip")
+        .body(hyper::Body::empty())?;
+    let response = service(request).await;
+    assert_eq!(response.status(), 206);
+    assert!(response.headers().get("content-encoding").is_none());
+    assert_eq!(hyper::body::to_bytes(response).await?.len(), 100);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_compression_streams_and_skips_compressed_types() -> Result<()> {
+    // A body of unknown length, sent in chunks
+    let service = theater::listener::service(|req: theater::listener::Request| async move {
+        let chunks = (0..64).map(|_| Ok::<_, std::io::Error>("0123456789abcdef".repeat(64)));
+        let mut response = theater::listener::Response::new(hyper::Body::wrap_stream(
+            futures::stream::iter(chunks),
+        ));
+        let content_type = if req.uri().path() == "/photo.png" {
+            "image/png"
+        } else {
+            "text/plain"
+        };
+        let headers = response.headers_mut();
+        headers.insert("Content-Type", content_type.parse().unwrap());
+        headers.insert("ETag", "\"v1\"".parse().unwrap());
+        response
+    });
+    let service =
+        theater::middleware::apply(service, &MiddlewareConfig::Compression { min_bytes: 10 })?;
+
+    let request = hyper::Request::get("/notes.txt")
+        .header("Accept-Encoding", "gzip")
+        .body(hyper::Body::empty())?;
+    let response = service.clone()(request).await;
+    assert_eq!(response.headers()["content-encoding"], "gzip");
+    // The encoded bytes are not the ones the strong validator was for
+    assert_eq!(response.headers()["etag"], "W/\"v1\"");
+    let mut body = Vec::new();
+    flate2::read::GzDecoder::new(&hyper::body::to_bytes(response).await?[..])
+        .read_to_end(&mut body)?;
+    assert_eq!(body, "0123456789abcdef".repeat(64 * 64).into_bytes());
+
+    let request = hyper::Request::get("/photo.png")
+        .header("Accept-Encoding", "gzip, br")
+        .body(hyper::Body::empty())?;
+    let response = service(request).await;
+    assert!(response.headers().get("content-encoding").is_none());
+    assert_eq!(response.headers()["etag"], "\"v1\"");
+    assert_eq!(hyper::body::to_bytes(response).await?.len(), 16 * 64 * 64);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_body_limit_without_content_length() -> Result<()> {
+    // The handler only sees a read error when the body runs past the limit
+    let service = theater::listener::service(|req: theater::listener::Request| async move {
+        let status = match hyper::body::to_bytes(req.into_body()).await {
+            Ok(_) => 200,
+            Err(_) => 400,
+        };
+        let mut response = theater::listener::Response::default();
+        *response.status_mut() = hyper::StatusCode::from_u16(status).unwrap();
+        response
+    });
+    let service =
+        theater::middleware::apply(service, &MiddlewareConfig::BodyLimit { max_bytes: 16 })?;
+
+    let chunked = |count: usize| {
+        let chunks = (0..count).map(|_| Ok::<_, std::io::Error>("0123"));
+        hyper::Request::post("/upload")
+            .body(hyper::Body::wrap_stream(futures::stream::iter(chunks)))
+    };
+    assert_eq!(service.clone()(chunked(4)?).await.status(), 200);
+    assert_eq!(service(chunked(5)?).await.status(), 413);
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use serde_json::json;
+use theater::registry::{ActorAddress, ActorRegistry, InspectError};
+use theater::ActorInput;
+use tokio::sync::mpsc;
+
+#[test]
+fn test_registry_resolution() {
+    let registry = ActorRegistry::new();
+    registry.register_remote("counter", "http://10.0.0.5:8080");
+
+    match registry.resolve("counter") {
+        Some(ActorAddress::Remote(url)) => assert_eq!(url, "http://10.0.0.5:8080"),
+        other => panic!("Unexpected resolution: {:?}", other),
+    }
+
+    // Raw URLs still resolve for actors that don't use names yet
+    assert!(matches!(
+        registry.resolve("http://localhost:8080"),
+        Some(ActorAddress::Remote(_))
+    ));
+
+    assert!(registry.resolve("missing").is_none());
+
+    registry.unregister("counter");
+    assert!(registry.resolve("counter").is_none());
+}
+
+#[tokio::test]
+async fn test_registry_local_delivery() -> Result<()> {
+    let registry = ActorRegistry::new();
+    let (tx, mut rx) = mpsc::channel(32);
+    registry.register_local("worker", tx.into());
+
+    registry
+        .send("worker", json!({"action": "ping"}), None)
+        .await?;
+
+    let received = rx.recv().await.expect("message not delivered");
+    match received.content {
+        ActorInput::Message(msg) => assert_eq!(msg["action"], "ping"),
+        other => panic!("Unexpected input: {:?}", other),
+    }
+
+    assert!(registry.send("missing", json!({}), None).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_failed_start_leaves_no_entry() -> Result<()> {
+    let config: theater::config::ManifestConfig = toml::from_str(
+        r#"
+name = "never-started"
+component_path = "missing.wasm"
+"#,
+    )?;
+
+    assert!(theater::ActorRuntime::from_config(config, None).is_err());
+    assert!(theater::registry::ACTOR_REGISTRY
+        .resolve("never-started")
+        .is_none());
+
+    Ok(())
+}
+
+// The clock is paused, so the control timeout passes as soon as nothing else can run
+#[tokio::test(start_paused = true)]
+async fn test_inspect_chain_times_out() -> Result<()> {
+    let registry = ActorRegistry::new();
+    // An actor that never gets to its control messages
+    let (control_tx, _control_rx) = mpsc::channel(1);
+    registry.register_control("busy", control_tx);
+
+    let result = registry.inspect_chain("busy", |chain| chain.len()).await;
+    assert!(matches!(result, Err(InspectError::Timeout(ref name)) if name == "busy"));
+
+    Ok(())
+}
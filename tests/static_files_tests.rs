@@ -0,0 +1,111 @@
+mod common;
+
+use anyhow::Result;
+use serde_json::json;
+use theater::http_server::HttpServerHandler;
+use theater::{ActorInput, ActorOutput};
+
+#[tokio::test]
+async fn test_static_files_with_fallthrough_to_actor() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("index.html"), "<h1>Hello</h1>")?;
+    std::fs::create_dir(dir.path().join("assets"))?;
+    std::fs::write(dir.path().join("assets/app.js"), "console.log('hi');")?;
+    let outside = tempfile::tempdir()?;
+    std::fs::write(outside.path().join("secret.txt"), "secret")?;
+    std::os::unix::fs::symlink(
+        outside.path().join("secret.txt"),
+        dir.path().join("leak.txt"),
+    )?;
+
+    let handler = HttpServerHandler::from_config(serde_json::from_value(json!({
+        "port": 18103,
+        "static_files": { "dir": dir.path() },
+    }))?);
+
+    // The actor answers with the path it was asked for
+    common::start_actor(handler, |input| {
+        let path = match input {
+            ActorInput::HttpRequest { path, .. } => path,
+            ActorInput::Message(_) => String::new(),
+        };
+        ActorOutput::HttpResponse {
+            status: 200,
+            headers: vec![],
+            body: Some(format!("actor {}", path).into_bytes()),
+        }
+    })
+    .await?;
+
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://127.0.0.1:18103{}", path);
+
+    // Directories serve their index file
+    let response = client.get(url("/")).send().await?;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["content-type"], "text/html");
+    assert_eq!(response.text().await?, "<h1>Hello</h1>");
+
+    let response = client.get(url("/assets/app.js")).send().await?;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["content-type"], "text/javascript");
+    assert_eq!(response.headers()["accept-ranges"], "bytes");
+    assert!(response.headers().contains_key("last-modified"));
+    let etag = response.headers()["etag"].clone();
+    assert_eq!(response.text().await?, "console.log('hi');");
+
+    // A cached copy with the current ETag is still fresh
+    let response = client
+        .get(url("/assets/app.js"))
+        .header("If-None-Match", etag.clone())
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 304);
+    assert_eq!(response.headers()["etag"], etag);
+    let response = client
+        .get(url("/assets/app.js"))
+        .header("If-None-Match", "\"stale\"")
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Ranges
+    let response = client
+        .get(url("/assets/app.js"))
+        .header("Range", "bytes=0-6")
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 206);
+    assert_eq!(response.headers()["content-range"], "bytes 0-6/18");
+    assert_eq!(response.text().await?, "console");
+    let response = client
+        .get(url("/assets/app.js"))
+        .header("Range", "bytes=-3")
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 206);
+    assert_eq!(response.text().await?, "');");
+    let response = client
+        .get(url("/assets/app.js"))
+        .header("Range", "bytes=100-")
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 416);
+    assert_eq!(response.headers()["content-range"], "bytes */18");
+
+    // Anything the directory can't answer goes to the actor
+    let response = client.get(url("/api/users")).send().await?;
+    assert_eq!(response.text().await?, "actor /api/users");
+    let response = client.post(url("/assets/app.js")).send().await?;
+    assert_eq!(response.text().await?, "actor /assets/app.js");
+    let response = client
+        .get(url("/assets/%2E%2E/%2E%2E/etc/passwd"))
+        .send()
+        .await?;
+    assert!(response.text().await?.starts_with("actor "));
+    // Symlinks are not followed out of the directory
+    let response = client.get(url("/leak.txt")).send().await?;
+    assert_eq!(response.text().await?, "actor /leak.txt");
+
+    Ok(())
+}
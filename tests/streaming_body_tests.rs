@@ -0,0 +1,137 @@
+mod common;
+
+use anyhow::Result;
+use hyper::Body;
+use serde_json::json;
+use theater::http_server::HttpServerHandler;
+use theater::{ActorInput, ActorOutput, MessageMetadata};
+use tokio::sync::mpsc;
+
+/// Start a handler whose actor answers with the number of body bytes it got,
+/// reading streamed bodies in 64 KiB chunks
+async fn start(config: serde_json::Value) -> Result<()> {
+    let handler = HttpServerHandler::from_config(serde_json::from_value(config)?);
+    let (tx, mut rx) = mpsc::channel(32);
+    common::start(handler, tx).await?;
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            let (
+                ActorInput::HttpRequest {
+                    body, body_stream, ..
+                },
+                Some(MessageMetadata::HttpRequest {
+                    response_channel, ..
+                }),
+            ) = (msg.content, msg.metadata)
+            else {
+                continue;
+            };
+
+            let (status, received) = match (body, body_stream) {
+                (Some(body), None) => (200, body.len()),
+                (None, Some(stream)) if stream.take() => {
+                    let mut received = 0;
+                    loop {
+                        match stream.read(64 * 1024) {
+                            Ok(Some(chunk)) => received += chunk.len(),
+                            Ok(None) => break (200, received),
+                            Err(_) => break (413, received),
+                        }
+                    }
+                }
+                _ => (500, 0),
+            };
+            let _ = response_channel.send(ActorOutput::HttpResponse {
+                status,
+                headers: vec![],
+                body: Some(received.to_string().into_bytes()),
+            });
+        }
+    });
+    Ok(())
+}
+
+/// POST a body sent with chunked transfer encoding, so it has no Content-Length
+async fn post_chunked(url: &str, chunk: &'static [u8], count: usize) -> Result<(u16, String)> {
+    let chunks = (0..count).map(move |_| Ok::<_, std::io::Error>(chunk));
+    let request =
+        hyper::Request::post(url).body(Body::wrap_stream(futures::stream::iter(chunks)))?;
+    let response = hyper::Client::new().request(request).await?;
+    let status = response.status().as_u16();
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    Ok((status, String::from_utf8(body.to_vec())?))
+}
+
+// The actor's reads block its thread, so they need the multi-threaded runtime
+#[tokio::test(flavor = "multi_thread")]
+async fn test_streamed_upload() -> Result<()> {
+    start(json!({
+        "port": 18104,
+        "stream_body": true,
+        "max_body_bytes": 32 * 1024 * 1024,
+    }))
+    .await?;
+
+    // 16 MiB in 1 MiB chunks
+    static CHUNK: [u8; 1024 * 1024] = [7; 1024 * 1024];
+    let (status, body) = post_chunked("http://127.0.0.1:18104/uploads", &CHUNK, 16).await?;
+    assert_eq!(status, 200);
+    assert_eq!(body, (16 * 1024 * 1024).to_string());
+
+    // Past the limit the actor's read fails
+    let (status, _) = post_chunked("http://127.0.0.1:18104/uploads", &CHUNK, 40).await?;
+    assert_eq!(status, 413);
+
+    // A declared length past the limit never reaches the actor
+    let response = reqwest::Client::new()
+        .post("http://127.0.0.1:18104/uploads")
+        .body(vec![0u8; 33 * 1024 * 1024])
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 413);
+    let body: serde_json::Value = response.json().await?;
+    assert!(body["correlation_id"].is_string());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_buffered_body_limit() -> Result<()> {
+    start(json!({ "port": 18105, "max_body_bytes": 1024 })).await?;
+
+    let (status, body) = post_chunked("http://127.0.0.1:18105/", b"0123456789", 100).await?;
+    assert_eq!((status, body.as_str()), (200, "1000"));
+
+    let (status, _) = post_chunked("http://127.0.0.1:18105/", b"0123456789", 103).await?;
+    assert_eq!(status, 413);
+
+    let response = reqwest::Client::new()
+        .post("http://127.0.0.1:18105/")
+        .body(vec![0u8; 1025])
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 413);
+
+    Ok(())
+}
+
+// The body-limit middleware counts a streamed body as the actor reads it
+#[tokio::test(flavor = "multi_thread")]
+async fn test_streamed_upload_through_body_limit() -> Result<()> {
+    start(json!({
+        "port": 18110,
+        "stream_body": true,
+        "middleware": [{ "type": "body-limit", "max_bytes": 8 * 1024 * 1024 }],
+    }))
+    .await?;
+
+    static CHUNK: [u8; 1024 * 1024] = [7; 1024 * 1024];
+    let (status, body) = post_chunked("http://127.0.0.1:18110/uploads", &CHUNK, 4).await?;
+    assert_eq!(status, 200);
+    assert_eq!(body, (4 * 1024 * 1024).to_string());
+
+    let (status, _) = post_chunked("http://127.0.0.1:18110/uploads", &CHUNK, 16).await?;
+    assert_eq!(status, 413);
+
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::time::Duration;
+use tempfile::{Builder, NamedTempFile};
+use theater::chain::ChainEvent;
+use theater::registry::ACTOR_REGISTRY;
+use theater::supervisor::Supervisor;
+use theater::{Actor, ActorInput, ActorOutput, ActorProcess};
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn test_spawn_missing_manifest() {
+    let (tx, _rx) = mpsc::channel(32);
+    let supervisor = Supervisor::new("parent", tx.into());
+
+    assert!(supervisor
+        .spawn_child("does/not/exist.toml", serde_json::Value::Null)
+        .is_err());
+
+    // Failed spawns leave nothing to commit to the parent's chain
+    assert!(supervisor.take_events().is_empty());
+}
+
+#[tokio::test]
+async fn test_stop_unknown_child() {
+    let (tx, _rx) = mpsc::channel(32);
+    let supervisor = Supervisor::new("parent", tx.into());
+
+    assert!(supervisor.stop_child("parent/missing-1").is_err());
+    assert!(supervisor.take_events().is_empty());
+}
+
+#[tokio::test]
+async fn test_child_exit_notification() {
+    let (tx, mut rx) = mpsc::channel(32);
+    let supervisor = Supervisor::new("parent", tx.into());
+
+    supervisor
+        .notify_child_exit("parent/worker-1", "stopped")
+        .await;
+
+    let msg = rx.recv().await.expect("notification not delivered");
+    match msg.content {
+        ActorInput::Message(value) => {
+            assert_eq!(value["type"], "child-exit");
+            assert_eq!(value["child"], "parent/worker-1");
+            assert_eq!(value["reason"], "stopped");
+        }
+        other => panic!("Unexpected input: {:?}", other),
+    }
+}
+
+/// Component for the plain actor interface that starts at state `0` and
+/// keeps its state whatever message it handles
+const CHILD_ACTOR: &str = r#"
+(component
+    (core module $m
+        (memory (export "memory") 1)
+        (global $bump (mut i32) (i32.const 8192))
+        (data (i32.const 2048) "0")
+
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+            (local $ret i32)
+            (local.set $ret
+                (i32.and (i32.add (global.get $bump) (i32.const 7)) (i32.const -8)))
+            (global.set $bump (i32.add (local.get $ret) (local.get 3)))
+            (local.get $ret))
+
+        (func (export "init") (result i32)
+            (i32.store (i32.const 1024) (i32.const 2048))
+            (i32.store (i32.const 1028) (i32.const 1))
+            (i32.const 1024))
+
+        (func (export "handle") (param i32 i32 i32 i32) (result i32)
+            (i32.store (i32.const 1024) (local.get 2))
+            (i32.store (i32.const 1028) (local.get 3))
+            (i32.const 1024))
+
+        (func (export "state-contract") (param i32 i32) (result i32) (i32.const 1))
+        (func (export "message-contract") (param i32 i32 i32 i32) (result i32) (i32.const 1))
+    )
+    (core instance $i (instantiate $m))
+    (alias core export $i "memory" (core memory $mem))
+    (alias core export $i "realloc" (core func $realloc))
+
+    (type $bytes (list u8))
+    (func $init (result $bytes)
+        (canon lift (core func $i "init") (memory $mem)))
+    (func $handle (param "msg" $bytes) (param "state" $bytes) (result $bytes)
+        (canon lift (core func $i "handle") (memory $mem) (realloc $realloc)))
+    (func $state-contract (param "state" $bytes) (result bool)
+        (canon lift (core func $i "state-contract") (memory $mem) (realloc $realloc)))
+    (func $message-contract (param "msg" $bytes) (param "state" $bytes) (result bool)
+        (canon lift (core func $i "message-contract") (memory $mem) (realloc $realloc)))
+
+    (instance $actor
+        (export "init" (func $init))
+        (export "handle" (func $handle))
+        (export "state-contract" (func $state-contract))
+        (export "message-contract" (func $message-contract)))
+    (export "ntwk:simple-actor/actor" (instance $actor))
+)
+"#;
+
+struct Parent;
+
+impl Actor for Parent {
+    fn init(&self) -> Result<Value> {
+        Ok(json!({}))
+    }
+
+    fn handle_input(&self, input: ActorInput, state: &Value) -> Result<(ActorOutput, Value)> {
+        match input {
+            ActorInput::Message(msg) => Ok((ActorOutput::Message(msg), state.clone())),
+            _ => anyhow::bail!("unexpected input"),
+        }
+    }
+
+    fn verify_state(&self, _state: &Value) -> bool {
+        true
+    }
+}
+
+fn child_component() -> Result<NamedTempFile> {
+    let mut component = Builder::new().suffix(".wat").tempfile()?;
+    write!(component, "{}", CHILD_ACTOR)?;
+    Ok(component)
+}
+
+#[tokio::test]
+async fn test_spawn_and_stop_child() -> Result<()> {
+    let component = child_component()?;
+    let mut manifest = Builder::new().suffix(".toml").tempfile()?;
+    write!(
+        manifest,
+        r#"
+name = "worker"
+component_path = "{}"
+
+[interface]
+implements = "ntwk:simple-actor/actor"
+requires = []
+"#,
+        component.path().display()
+    )?;
+
+    let (tx, mut rx) = mpsc::channel(32);
+    let supervisor = Supervisor::new("spawning-parent", tx.into());
+    let manifest_path = manifest.path().display().to_string();
+    let child_id = supervisor.spawn_child(&manifest_path, json!({"job": 1}))?;
+    assert!(child_id.starts_with("spawning-parent/worker-"));
+
+    // The child's chain links back to its parent
+    let parent_link = ACTOR_REGISTRY
+        .inspect_chain(&child_id, |chain| {
+            chain.get_full_chain().into_iter().any(|(_, entry)| {
+                matches!(
+                    entry.event,
+                    ChainEvent::ParentLink { ref parent_actor, .. }
+                        if parent_actor == "spawning-parent"
+                )
+            })
+        })
+        .await?;
+    assert!(parent_link);
+
+    supervisor.stop_child(&child_id)?;
+
+    let msg = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await?
+        .expect("exit notification not delivered");
+    match msg.content {
+        ActorInput::Message(value) => {
+            assert_eq!(value["type"], "child-exit");
+            assert_eq!(value["child"], child_id.as_str());
+            assert_eq!(value["reason"], "stopped");
+        }
+        other => panic!("Unexpected input: {:?}", other),
+    }
+
+    // The parent's process commits the spawn and stop to its own chain
+    let process = ActorProcess::new(&"spawning-parent".to_string(), Box::new(Parent), rx)?
+        .with_supervisor(supervisor);
+    let events: Vec<_> = process
+        .get_chain()
+        .get_full_chain()
+        .into_iter()
+        .map(|(_, entry)| entry.event)
+        .collect();
+    assert!(events.iter().any(|event| matches!(
+        event,
+        ChainEvent::ChildSpawned { child_id: id, manifest, .. }
+            if *id == child_id && *manifest == manifest_path
+    )));
+    assert!(events.iter().any(|event| matches!(
+        event,
+        ChainEvent::ChildStopped { child_id: id, .. } if *id == child_id
+    )));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_spawn_child_from_component() -> Result<()> {
+    let component = child_component()?;
+    let (tx, mut rx) = mpsc::channel(32);
+    let supervisor = Supervisor::new("component-parent", tx.into());
+
+    let stem = component.path().file_stem().unwrap().to_string_lossy();
+    let child_id = supervisor.spawn_child(&component.path().display().to_string(), Value::Null)?;
+    assert!(child_id.starts_with(&format!("component-parent/{}-", stem)));
+
+    // A running child answers through its control channel
+    let length = ACTOR_REGISTRY
+        .inspect_chain(&child_id, |chain| chain.len())
+        .await?;
+    assert!(length > 0);
+
+    // A child that has exited is forgotten, so it cannot be stopped again
+    supervisor.notify_child_exit(&child_id, "completed").await;
+    assert!(rx.recv().await.is_some());
+    assert!(supervisor.stop_child(&child_id).is_err());
+
+    Ok(())
+}
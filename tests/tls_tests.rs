@@ -0,0 +1,102 @@
+mod common;
+
+use anyhow::Result;
+use serde_json::json;
+use std::path::PathBuf;
+use std::time::Duration;
+use theater::config::{HttpHandlerConfig, HttpServerHandlerConfig, TlsConfig};
+use theater::http::{HttpHandler, HttpHost};
+use theater::http_server::HttpServerHandler;
+use theater::ActorInput;
+use tokio::sync::mpsc;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/tls")
+        .join(name)
+}
+
+fn mutual_tls(identity: &str) -> TlsConfig {
+    TlsConfig {
+        cert_path: fixture(&format!("{}.pem", identity)),
+        key_path: fixture(&format!("{}.key", identity)),
+        client_ca_path: Some(fixture("ca.pem")),
+    }
+}
+
+fn client(identity: Option<&str>) -> Result<reqwest::Client> {
+    let ca = reqwest::Certificate::from_pem(&std::fs::read(fixture("ca.pem"))?)?;
+    let mut builder = reqwest::Client::builder().add_root_certificate(ca);
+    if let Some(identity) = identity {
+        builder = builder.identity(reqwest::Identity::from_pkcs8_pem(
+            &std::fs::read(fixture(&format!("{}.pem", identity)))?,
+            &std::fs::read(fixture(&format!("{}.key", identity)))?,
+        )?);
+    }
+    Ok(builder.build()?)
+}
+
+#[tokio::test]
+async fn test_http_server_requires_client_certificate() -> Result<()> {
+    let mut config: HttpServerHandlerConfig = serde_json::from_value(json!({ "port": 18099 }))?;
+    config.tls = Some(mutual_tls("server"));
+    let handler = HttpServerHandler::from_config(config);
+
+    common::start_echo(handler).await?;
+
+    let response = client(Some("client"))?
+        .get("https://127.0.0.1:18099/status")
+        .send()
+        .await?;
+    assert_eq!(response.status().as_u16(), 200);
+    match response.json::<ActorInput>().await? {
+        ActorInput::HttpRequest { uri, peer_addr, .. } => {
+            assert_eq!(uri, "https://127.0.0.1:18099/status");
+            assert!(peer_addr.unwrap().starts_with("127.0.0.1:"));
+        }
+        other => panic!("Unexpected input: {:?}", other),
+    }
+
+    // Without a certificate the handshake is refused
+    assert!(client(None)?
+        .get("https://127.0.0.1:18099/status")
+        .send()
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_actors_exchange_messages_over_mutual_tls() -> Result<()> {
+    let handler = HttpHandler::from_config(HttpHandlerConfig {
+        port: 18100,
+        address: "127.0.0.1".to_string(),
+        path_prefix: None,
+        tls: Some(mutual_tls("server")),
+        auth: None,
+    });
+
+    let (tx, mut rx) = mpsc::channel(32);
+    common::start(handler, tx).await?;
+
+    // The sending actor presents its own certificate
+    let (sender_tx, _sender_rx) = mpsc::channel(32);
+    let sender = HttpHost::new(sender_tx.into()).with_tls(&mutual_tls("client"))?;
+    sender
+        .send_message(
+            "https://127.0.0.1:18100/".to_string(),
+            json!({"action": "ping"}),
+        )
+        .await?;
+
+    let msg = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+        .await?
+        .unwrap();
+    match msg.content {
+        ActorInput::Message(payload) => assert_eq!(payload, json!({"action": "ping"})),
+        other => panic!("Unexpected input: {:?}", other),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use hyper::Body;
+use opentelemetry::trace::TraceId;
+use opentelemetry::Value as AttributeValue;
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry_sdk::trace::TracerProvider;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use theater::listener;
+use theater::telemetry;
+use theater::{Actor, ActorInput, ActorMessage, ActorOutput, ActorProcess};
+use tokio::sync::mpsc;
+use tracing_subscriber::layer::SubscriberExt;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+/// Keeps every exported span in memory
+#[derive(Clone, Debug, Default)]
+struct Exported(Arc<Mutex<Vec<SpanData>>>);
+
+impl SpanExporter for Exported {
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        self.0.lock().unwrap().extend(batch);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+impl Exported {
+    fn span(&self, name: &str) -> SpanData {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|span| span.name == name)
+            .cloned()
+            .unwrap_or_else(|| panic!("no {} span was exported", name))
+    }
+}
+
+struct Echo;
+
+impl Actor for Echo {
+    fn init(&self) -> Result<Value> {
+        Ok(json!(0))
+    }
+
+    fn handle_input(&self, input: ActorInput, state: &Value) -> Result<(ActorOutput, Value)> {
+        match input {
+            ActorInput::Message(msg) => Ok((ActorOutput::Message(msg), state.clone())),
+            _ => anyhow::bail!("unexpected input"),
+        }
+    }
+
+    fn verify_state(&self, _state: &Value) -> bool {
+        true
+    }
+}
+
+#[tokio::test]
+async fn test_trace_follows_request_into_actor() -> Result<()> {
+    let exported = Exported::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exported.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry().with(telemetry::layer(&provider));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let (tx, rx) = mpsc::channel(8);
+    let service = listener::service(move |_req: listener::Request| {
+        let tx = tx.clone();
+        async move {
+            let msg = ActorMessage::new(ActorInput::Message(json!("traced")), None);
+            tx.send(msg).await.unwrap();
+            listener::Response::default()
+        }
+    });
+    let service = telemetry::traced(service, "test");
+
+    let request = hyper::Request::post("/")
+        .header(
+            "traceparent",
+            format!("00-{}-00f067aa0ba902b7-01", TRACE_ID),
+        )
+        .body(Body::empty())?;
+    service(request).await;
+    drop(service);
+
+    let mut process = ActorProcess::new(&"traced".to_string(), Box::new(Echo), rx)?;
+    process.run().await?;
+    let head = process.get_chain().get_head().unwrap().to_string();
+    drop(process);
+    provider.force_flush();
+
+    // The actor continues the caller's trace from the handler's span
+    let request = exported.span("http.request");
+    let handle = exported.span("actor.handle");
+    let trace_id = TraceId::from_hex(TRACE_ID)?;
+    assert_eq!(request.span_context.trace_id(), trace_id);
+    assert_eq!(handle.span_context.trace_id(), trace_id);
+    assert_eq!(handle.parent_span_id, request.span_context.span_id());
+
+    // The output's chain hash is attached to the handling span
+    let hash = handle
+        .attributes
+        .iter()
+        .find(|attribute| attribute.key.as_str() == "chain.hash")
+        .map(|attribute| attribute.value.clone());
+    assert_eq!(hash, Some(AttributeValue::from(head)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_trace_headers_carry_current_trace() -> Result<()> {
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(Exported::default())
+        .build();
+    let subscriber = tracing_subscriber::registry().with(telemetry::layer(&provider));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let request = hyper::Request::get("/")
+        .header(
+            "traceparent",
+            format!("00-{}-00f067aa0ba902b7-01", TRACE_ID),
+        )
+        .body(Body::empty())?;
+    let span = telemetry::request_span("test", &request);
+    let headers = telemetry::trace_headers(&span);
+
+    let traceparent = headers["traceparent"].to_str()?;
+    assert!(traceparent.starts_with(&format!("00-{}-", TRACE_ID)));
+    // The span's own id replaces the caller's
+    assert!(!traceparent.contains("00f067aa0ba902b7"));
+
+    Ok(())
+}
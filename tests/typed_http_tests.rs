@@ -0,0 +1,372 @@
+use anyhow::Result;
+use hyper::Body;
+use serde_json::json;
+use std::io::Write;
+use tempfile::{Builder, NamedTempFile};
+use theater::config::ManifestConfig;
+use theater::http_server::RequestBody;
+use theater::metrics::METRICS;
+use theater::{Actor, ActorInput, ActorOutput, Store, WasmActor};
+
+// Answers every request with status 200 + method discriminant, a `path` header
+// echoing the request path, the request body, and the state it was given.
+const TYPED_HTTP_ACTOR: &str = r#"
+(component
+    (core module $m
+        (memory (export "memory") 1)
+        (global $bump (mut i32) (i32.const 4096))
+        (data (i32.const 1200) "path")
+
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+            (local $ret i32)
+            (local.set $ret
+                (i32.and (i32.add (global.get $bump) (i32.const 7)) (i32.const -8)))
+            (global.set $bump (i32.add (local.get $ret) (local.get 3)))
+            (local.get $ret))
+
+        (func (export "handle-http") (param $args i32) (result i32)
+            ;; status
+            (i32.store16 (i32.const 1024)
+                (i32.add (i32.const 200) (i32.load8_u (local.get $args))))
+            ;; headers: [("path", request.path)]
+            (i32.store (i32.const 1028) (i32.const 1100))
+            (i32.store (i32.const 1032) (i32.const 1))
+            (i32.store (i32.const 1100) (i32.const 1200))
+            (i32.store (i32.const 1104) (i32.const 4))
+            (i64.store (i32.const 1108) (i64.load offset=20 (local.get $args)))
+            ;; body
+            (i32.store8 (i32.const 1036) (i32.load8_u offset=44 (local.get $args)))
+            (i64.store (i32.const 1040) (i64.load offset=48 (local.get $args)))
+            ;; state
+            (i64.store (i32.const 1048) (i64.load offset=112 (local.get $args)))
+            (i32.const 1024))
+
+        (func (export "init") (result i32) unreachable)
+        (func (export "handle") (param i32 i32 i32 i32) (result i32) unreachable)
+        (func (export "state-contract") (param i32 i32) (result i32) unreachable)
+        (func (export "message-contract") (param i32 i32 i32 i32) (result i32) unreachable)
+        (func (export "http-contract") (param i32) (result i32) unreachable)
+    )
+    (core instance $i (instantiate $m))
+    (alias core export $i "memory" (core memory $mem))
+    (alias core export $i "realloc" (core func $realloc))
+
+    (type $method (variant
+        (case "get") (case "head") (case "post") (case "put") (case "delete")
+        (case "connect") (case "options") (case "trace") (case "patch")
+        (case "other" string)))
+    (type $headers (list (tuple string string)))
+    (type $request (record
+        (field "method" $method)
+        (field "uri" string)
+        (field "path" string)
+        (field "query" (list (tuple string string)))
+        (field "headers" $headers)
+        (field "body" (option (list u8)))
+        (field "peer-addr" (option string))
+        (field "version" (option string))
+        (field "params" (list (tuple string string)))
+        (field "route-tag" (option string))
+        (field "principal" (option string))))
+    (type $response (record
+        (field "status" u16)
+        (field "headers" $headers)
+        (field "body" (option (list u8)))))
+    (type $state (list u8))
+
+    (func $handle-http
+        (param "req" $request) (param "state" $state) (result (tuple $response $state))
+        (canon lift (core func $i "handle-http") (memory $mem) (realloc $realloc)))
+    (func $init (result $state)
+        (canon lift (core func $i "init") (memory $mem)))
+    (func $handle (param "msg" $state) (param "state" $state) (result $state)
+        (canon lift (core func $i "handle") (memory $mem) (realloc $realloc)))
+    (func $state-contract (param "state" $state) (result bool)
+        (canon lift (core func $i "state-contract") (memory $mem) (realloc $realloc)))
+    (func $message-contract (param "msg" $state) (param "state" $state) (result bool)
+        (canon lift (core func $i "message-contract") (memory $mem) (realloc $realloc)))
+    (func $http-contract (param "req" $request) (param "state" $state) (result bool)
+        (canon lift (core func $i "http-contract") (memory $mem) (realloc $realloc)))
+
+    (instance $actor
+        (export "method" (type $method))
+        (export "http-request" (type $request))
+        (export "http-response" (type $response))
+        (export "init" (func $init))
+        (export "handle" (func $handle))
+        (export "state-contract" (func $state-contract))
+        (export "message-contract" (func $message-contract))
+        (export "http-contract" (func $http-contract))
+        (export "handle-http" (func $handle-http)))
+    (export "ntwk:simple-http-actor/http-actor@0.2.0" (instance $actor))
+)
+"#;
+
+// Reads the streamed request body four bytes at a time, answering with status
+// 200 + the number of reads (413 + reads if a read fails) and the bytes read.
+const STREAMING_HTTP_ACTOR: &str = r#"
+(component
+    (import "ntwk:simple-http-actor/http-runtime@0.2.0" (instance $runtime
+        (export $body "request-body" (type (sub resource)))
+        (export "take-request-body" (func (result (option (own $body)))))
+        (export "[method]request-body.read" (func
+            (param "self" (borrow $body)) (param "max-bytes" u32)
+            (result (result (option (list u8)) (error string)))))
+    ))
+    (alias export $runtime "request-body" (type $request-body))
+    (alias export $runtime "take-request-body" (func $take-request-body))
+    (alias export $runtime "[method]request-body.read" (func $read-request-body))
+
+    (core module $libc
+        (memory (export "memory") 1)
+        (global $bump (mut i32) (i32.const 4096))
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+            (local $ret i32)
+            (local.set $ret
+                (i32.and (i32.add (global.get $bump) (i32.const 7)) (i32.const -8)))
+            (global.set $bump (i32.add (local.get $ret) (local.get 3)))
+            (local.get $ret))
+    )
+    (core instance $libc (instantiate $libc))
+    (alias core export $libc "memory" (core memory $mem))
+    (alias core export $libc "realloc" (core func $realloc))
+    (core func $take (canon lower (func $take-request-body) (memory $mem)))
+    (core func $read (canon lower (func $read-request-body) (memory $mem) (realloc $realloc)))
+    (core func $drop (canon resource.drop $request-body))
+
+    (core module $m
+        (import "libc" "memory" (memory 1))
+        (import "runtime" "take" (func $take (param i32)))
+        (import "runtime" "read" (func $read (param i32 i32 i32)))
+        (import "runtime" "drop" (func $drop (param i32)))
+
+        (func (export "handle-http") (param $args i32) (result i32)
+            (local $body i32) (local $status i32) (local $reads i32) (local $total i32)
+            (local.set $status (i32.const 200))
+            (call $take (i32.const 900))
+            (if (i32.load8_u (i32.const 900))
+                (then
+                    (local.set $body (i32.load (i32.const 904)))
+                    (block $done
+                        (loop $next
+                            (call $read (local.get $body) (i32.const 4) (i32.const 920))
+                            (if (i32.load8_u (i32.const 920))
+                                (then
+                                    (local.set $status (i32.const 413))
+                                    (br $done)))
+                            (br_if $done (i32.eqz (i32.load8_u (i32.const 924))))
+                            (memory.copy
+                                (i32.add (i32.const 2048) (local.get $total))
+                                (i32.load (i32.const 928))
+                                (i32.load (i32.const 932)))
+                            (local.set $total
+                                (i32.add (local.get $total) (i32.load (i32.const 932))))
+                            (local.set $reads (i32.add (local.get $reads) (i32.const 1)))
+                            (br $next)))
+                    (call $drop (local.get $body))))
+            ;; status
+            (i32.store16 (i32.const 1024) (i32.add (local.get $status) (local.get $reads)))
+            ;; no headers
+            (i32.store (i32.const 1028) (i32.const 0))
+            (i32.store (i32.const 1032) (i32.const 0))
+            ;; body: the bytes read
+            (i32.store8 (i32.const 1036) (i32.const 1))
+            (i32.store (i32.const 1040) (i32.const 2048))
+            (i32.store (i32.const 1044) (local.get $total))
+            ;; state
+            (i64.store (i32.const 1048) (i64.load offset=112 (local.get $args)))
+            (i32.const 1024))
+
+        (func (export "init") (result i32) unreachable)
+        (func (export "handle") (param i32 i32 i32 i32) (result i32) unreachable)
+        (func (export "state-contract") (param i32 i32) (result i32) unreachable)
+        (func (export "message-contract") (param i32 i32 i32 i32) (result i32) unreachable)
+        (func (export "http-contract") (param i32) (result i32) unreachable)
+    )
+    (core instance $i (instantiate $m
+        (with "libc" (instance $libc))
+        (with "runtime" (instance
+            (export "take" (func $take))
+            (export "read" (func $read))
+            (export "drop" (func $drop))
+        ))
+    ))
+
+    (type $method (variant
+        (case "get") (case "head") (case "post") (case "put") (case "delete")
+        (case "connect") (case "options") (case "trace") (case "patch")
+        (case "other" string)))
+    (type $headers (list (tuple string string)))
+    (type $request (record
+        (field "method" $method)
+        (field "uri" string)
+        (field "path" string)
+        (field "query" (list (tuple string string)))
+        (field "headers" $headers)
+        (field "body" (option (list u8)))
+        (field "peer-addr" (option string))
+        (field "version" (option string))
+        (field "params" (list (tuple string string)))
+        (field "route-tag" (option string))
+        (field "principal" (option string))))
+    (type $response (record
+        (field "status" u16)
+        (field "headers" $headers)
+        (field "body" (option (list u8)))))
+    (type $state (list u8))
+
+    (func $handle-http
+        (param "req" $request) (param "state" $state) (result (tuple $response $state))
+        (canon lift (core func $i "handle-http") (memory $mem) (realloc $realloc)))
+    (func $init (result $state)
+        (canon lift (core func $i "init") (memory $mem)))
+    (func $handle (param "msg" $state) (param "state" $state) (result $state)
+        (canon lift (core func $i "handle") (memory $mem) (realloc $realloc)))
+    (func $state-contract (param "state" $state) (result bool)
+        (canon lift (core func $i "state-contract") (memory $mem) (realloc $realloc)))
+    (func $message-contract (param "msg" $state) (param "state" $state) (result bool)
+        (canon lift (core func $i "message-contract") (memory $mem) (realloc $realloc)))
+    (func $http-contract (param "req" $request) (param "state" $state) (result bool)
+        (canon lift (core func $i "http-contract") (memory $mem) (realloc $realloc)))
+
+    (instance $actor
+        (export "method" (type $method))
+        (export "http-request" (type $request))
+        (export "http-response" (type $response))
+        (export "init" (func $init))
+        (export "handle" (func $handle))
+        (export "state-contract" (func $state-contract))
+        (export "message-contract" (func $message-contract))
+        (export "http-contract" (func $http-contract))
+        (export "handle-http" (func $handle-http)))
+    (export "ntwk:simple-http-actor/http-actor@0.2.0" (instance $actor))
+)
+"#;
+
+/// Load a component implementing the typed HTTP actor interface
+fn typed_actor(wat: &str) -> Result<WasmActor> {
+    let mut component = Builder::new().suffix(".wat").tempfile()?;
+    write!(component, "{}", wat)?;
+
+    let mut manifest = NamedTempFile::new()?;
+    write!(
+        manifest,
+        r#"
+name = "typed-http-actor"
+component_path = "{}"
+
+[interface]
+implements = "ntwk:simple-http-actor/http-actor@0.2.0"
+requires = []
+
+[metrics]
+fuel = true
+"#,
+        component.path().display()
+    )?;
+
+    let config = ManifestConfig::from_file(manifest.path())?;
+    WasmActor::new(&config, Store::new())
+}
+
+#[test]
+fn test_typed_http_request_and_response() -> Result<()> {
+    let actor = typed_actor(TYPED_HTTP_ACTOR)?;
+
+    let (output, new_state) = actor.handle_input(
+        ActorInput::HttpRequest {
+            method: "POST".to_string(),
+            uri: "http://127.0.0.1:8081/images?size=large".to_string(),
+            path: "/images".to_string(),
+            query: vec![("size".to_string(), "large".to_string())],
+            params: vec![],
+            headers: vec![("Content-Type".to_string(), "image/png".to_string())],
+            body: Some(vec![0x89, b'P', b'N', b'G', 0x00, 0xff]),
+            body_stream: None,
+            peer_addr: Some("127.0.0.1:52814".to_string()),
+            version: Some("HTTP/1.1".to_string()),
+            route_tag: None,
+            principal: None,
+        },
+        &json!({"count": 1}),
+    )?;
+
+    match output {
+        ActorOutput::HttpResponse {
+            status,
+            headers,
+            body,
+        } => {
+            assert_eq!(status, 202);
+            assert_eq!(headers, vec![("path".to_string(), "/images".to_string())]);
+            assert_eq!(body, Some(vec![0x89, b'P', b'N', b'G', 0x00, 0xff]));
+        }
+        other => panic!("Unexpected output: {:?}", other),
+    }
+    assert_eq!(new_state, json!({"count": 1}));
+
+    // Each call instantiates the component and is charged for its fuel
+    let labels = [("actor", "typed-http-actor")];
+    assert!(METRICS.wasm_instantiation.count(&labels) > 0);
+    let fuel = [("actor", "typed-http-actor"), ("export", "handle-http")];
+    assert!(METRICS.fuel_consumed.get(&fuel) > 0);
+
+    // The component's state contract traps, so every state is rejected
+    assert!(!actor.verify_state(&json!({"count": 1})));
+    assert!(METRICS.contract_rejections.get(&labels) > 0);
+
+    Ok(())
+}
+
+fn upload(body: Option<RequestBody>) -> ActorInput {
+    ActorInput::HttpRequest {
+        method: "PUT".to_string(),
+        uri: "http://127.0.0.1:8081/uploads".to_string(),
+        path: "/uploads".to_string(),
+        query: vec![],
+        params: vec![],
+        headers: vec![],
+        body: None,
+        body_stream: body,
+        peer_addr: None,
+        version: Some("HTTP/1.1".to_string()),
+        route_tag: None,
+        principal: None,
+    }
+}
+
+// Reads block the thread, so they need the multi-threaded runtime
+#[tokio::test(flavor = "multi_thread")]
+async fn test_actor_reads_streamed_body() -> Result<()> {
+    let actor = typed_actor(STREAMING_HTTP_ACTOR)?;
+    let state = json!({});
+
+    let chunks: Vec<Result<&str, std::io::Error>> = vec![Ok("hello "), Ok("world")];
+    let body = RequestBody::new(Body::wrap_stream(futures::stream::iter(chunks)), None);
+    match actor.handle_input(upload(Some(body)), &state)?.0 {
+        ActorOutput::HttpResponse { status, body, .. } => {
+            // "hell", "o ", "worl", "d"
+            assert_eq!(status, 204);
+            assert_eq!(body, Some(b"hello world".to_vec()));
+        }
+        other => panic!("Unexpected output: {:?}", other),
+    }
+
+    // Reading past the handler's limit fails
+    let body = RequestBody::new(Body::from("hello world"), Some(5));
+    match actor.handle_input(upload(Some(body)), &state)?.0 {
+        ActorOutput::HttpResponse { status, .. } => assert_eq!(status, 413),
+        other => panic!("Unexpected output: {:?}", other),
+    }
+
+    // Nothing to take when the handler buffered the body
+    match actor.handle_input(upload(None), &state)?.0 {
+        ActorOutput::HttpResponse { status, body, .. } => {
+            assert_eq!(status, 200);
+            assert_eq!(body, Some(vec![]));
+        }
+        other => panic!("Unexpected output: {:?}", other),
+    }
+
+    Ok(())
+}